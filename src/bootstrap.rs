@@ -0,0 +1,196 @@
+use miette::{Context, bail};
+use tracing::{info, instrument, warn};
+
+use crate::{
+  config::{Config, INSTITUTION_LIST_KEY, PRESENTER_LIST_KEY},
+  guidebook::{
+    Modification, fetch_all_guidebook_entities, fetch_guidebook_entity,
+    model::{GuidebookCustomList, GuidebookGuide},
+    upsert_guidebook_entity,
+  },
+};
+
+/// Prepares a guide for syncing: checks that the guide exists, then finds or
+/// creates the presenter custom list, the institutions list if enabled and
+/// every configured custom list, and records their IDs in the store so they
+/// needn't be configured.
+#[instrument(skip(config))]
+pub async fn bootstrap(config: &Config) -> miette::Result<()> {
+  let guide = fetch_guidebook_entity::<GuidebookGuide>(
+    config,
+    "/guides",
+    config.guide_id as u32,
+  )
+  .await
+  .context("failed to find guide, so check `GUIDE_ID` and `API_KEY`")?;
+  info!(id = guide.id, name = guide.name, "found guide");
+
+  let mut existing_lists = fetch_all_guidebook_entities::<GuidebookCustomList>(
+    config,
+    "/custom-lists",
+  )
+  .await?;
+
+  for (key, name, configured_id) in configured_lists(config) {
+    let list_id = provision_custom_list(
+      config,
+      &mut existing_lists,
+      key,
+      name,
+      configured_id,
+    )
+    .await
+    .context(format!("failed to provision custom list {key:?}"))?;
+    config
+      .store
+      .record_custom_list_id(config.guide_id as u32, key, list_id)
+      .context("failed to record custom list ID")?;
+  }
+
+  Ok(())
+}
+
+/// Checks that every custom list used by its ID recorded in the store, rather
+/// than a configured one, still belongs to the configured guide.
+#[instrument(skip(config))]
+pub async fn check_recorded_custom_lists(
+  config: &Config,
+) -> miette::Result<()> {
+  for (key, _, configured_id) in configured_lists(config) {
+    if configured_id.is_some() {
+      continue;
+    }
+    let Some(list_id) = config
+      .store
+      .custom_list_id(config.guide_id as u32, key)
+      .context("failed to read custom list ID from store")?
+    else {
+      continue;
+    };
+
+    let list = fetch_guidebook_entity::<GuidebookCustomList>(
+      config,
+      "/custom-lists",
+      list_id,
+    )
+    .await
+    .context(format!(
+      "failed to fetch recorded custom list {key:?} with ID {list_id}, so \
+       rerun `bootstrap`"
+    ))?;
+    if list.guide_id as usize != config.guide_id {
+      bail!(
+        "found recorded custom list {key:?} with ID {list_id} in guide {}, \
+         not guide {}, so rerun `bootstrap`",
+        list.guide_id,
+        config.guide_id
+      );
+    }
+  }
+
+  Ok(())
+}
+
+/// The key, name and configured ID of the presenter list, the institutions
+/// list if enabled and every configured custom list.
+fn configured_lists(config: &Config) -> Vec<(&str, &str, Option<u32>)> {
+  let mut lists = vec![(
+    PRESENTER_LIST_KEY,
+    config.settings.presenter_list_name.as_str(),
+    config.presenter_custom_list_id,
+  )];
+  let mut custom_lists =
+    config.settings.custom_lists.iter().collect::<Vec<_>>();
+  custom_lists.sort_by_key(|(key, _)| *key);
+  if let Some(institution_settings) = &config.settings.institutions {
+    lists.push((
+      INSTITUTION_LIST_KEY,
+      institution_settings.name.as_str(),
+      institution_settings.list_id,
+    ));
+  }
+  lists.extend(custom_lists.into_iter().map(|(key, list_settings)| {
+    (
+      key.as_str(),
+      list_settings.name.as_deref().unwrap_or(key),
+      list_settings.list_id,
+    )
+  }));
+
+  lists
+}
+
+/// Finds the custom list for a key, first by its configured ID, then by the ID
+/// recorded by an earlier bootstrap, then by name, and creates it otherwise.
+async fn provision_custom_list(
+  config: &Config,
+  existing_lists: &mut Vec<GuidebookCustomList>,
+  key: &str,
+  name: &str,
+  configured_id: Option<u32>,
+) -> miette::Result<u32> {
+  let exists = |id: u32| {
+    existing_lists
+      .iter()
+      .any(|l| l.id == Some(id) && l.guide_id as usize == config.guide_id)
+  };
+
+  if let Some(list_id) = configured_id {
+    if !exists(list_id) {
+      bail!("found no custom list with configured ID {list_id} in the guide");
+    }
+    info!(key, list_id, "found configured custom list");
+    return Ok(list_id);
+  }
+
+  // the builder may have renamed a list provisioned before
+  let recorded_id = config
+    .store
+    .custom_list_id(config.guide_id as u32, key)
+    .context("failed to read custom list ID from store")?;
+  if let Some(list_id) = recorded_id {
+    if exists(list_id) {
+      info!(key, list_id, "found previously provisioned custom list");
+      return Ok(list_id);
+    }
+    warn!(
+      key,
+      list_id, "found previously provisioned custom list deleted from guide"
+    );
+  }
+
+  let named = existing_lists
+    .iter()
+    .filter(|l| l.name.trim().eq_ignore_ascii_case(name.trim()))
+    .filter_map(|l| l.id)
+    .collect::<Vec<_>>();
+  match named.as_slice() {
+    [list_id] => {
+      info!(key, list_id, name, "found custom list by name");
+      return Ok(*list_id);
+    }
+    [] => {}
+    ids => bail!(
+      "found several custom lists named {name:?}, with IDs {ids:?}, so set \
+       the one to use"
+    ),
+  }
+
+  let new_list = upsert_guidebook_entity(
+    config,
+    GuidebookCustomList {
+      id:       None,
+      guide_id: config.guide_id as u32,
+      name:     name.to_owned(),
+    },
+    "/custom-lists/",
+    Modification::Create,
+  )
+  .await
+  .context("failed to create custom list")?;
+  let list_id = new_list.id.unwrap();
+  info!(key, list_id, name, "created custom list");
+  existing_lists.push(new_list);
+
+  Ok(list_id)
+}
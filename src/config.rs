@@ -1,13 +1,29 @@
-use miette::{Context, IntoDiagnostic};
+use std::path::PathBuf;
+
+use miette::{Context, IntoDiagnostic, miette};
+
+use crate::{overrides::Overrides, settings::Settings, store::Store};
+
+/// The key the presenter custom list is provisioned under in the store.
+pub const PRESENTER_LIST_KEY: &str = "presenters";
+/// The key the institutions custom list is provisioned under in the store,
+/// which also namespaces the import IDs of its items.
+pub const INSTITUTION_LIST_KEY: &str = "institutions";
 
 #[derive(Debug)]
 pub struct Config {
   pub guide_id: usize,
   pub api_key: String,
-  pub presenter_custom_list_id: usize,
+  /// Without one, the list `bootstrap` found or created is used.
+  pub presenter_custom_list_id: Option<u32>,
   pub spreadsheet_id_sessions: String,
   pub spreadsheet_id_presenter_institutions: String,
   pub spreadsheet_id_strands: String,
+  pub store: Store,
+  pub overrides: Overrides,
+  pub settings: Settings,
+  pub verify_after_sync: bool,
+  pub headshots_dir: PathBuf,
 }
 
 impl Config {
@@ -25,10 +41,9 @@ impl Config {
       .context("missing `API_KEY` env var")?;
 
     let presenter_custom_list_id = std::env::var("PRESENTER_CUSTOM_LIST_ID")
-      .into_diagnostic()
-      .context("missing `PRESENTER_CUSTOM_LIST_ID` env var")?;
-    let presenter_custom_list_id = presenter_custom_list_id
-      .parse::<usize>()
+      .ok()
+      .map(|id| id.parse::<u32>())
+      .transpose()
       .into_diagnostic()
       .context("failed to parse presenter custom list ID")?;
 
@@ -43,6 +58,31 @@ impl Config {
       .into_diagnostic()
       .context("missing `SPREADSHEET_ID_STRANDS` env var")?;
 
+    let store_path = std::env::var("STORE_PATH")
+      .map(PathBuf::from)
+      .unwrap_or_else(|_| PathBuf::from("nasup-guidebook.sqlite3"));
+    let store = Store::open(&store_path).context("failed to open store")?;
+
+    let overrides = match std::env::var("OVERRIDES_PATH") {
+      Ok(path) => Overrides::from_file(path.as_ref())
+        .context("failed to load overrides")?,
+      Err(_) => Overrides::default(),
+    };
+
+    let settings = match std::env::var("SETTINGS_PATH") {
+      Ok(path) => {
+        Settings::from_file(path.as_ref()).context("failed to load settings")?
+      }
+      Err(_) => Settings::default(),
+    };
+
+    let verify_after_sync = std::env::var("VERIFY_AFTER_SYNC")
+      .is_ok_and(|v| matches!(v.as_str(), "1" | "true"));
+
+    let headshots_dir = std::env::var("HEADSHOTS_DIR")
+      .map(PathBuf::from)
+      .unwrap_or_else(|_| PathBuf::from("headshots"));
+
     Ok(Self {
       guide_id,
       api_key,
@@ -50,6 +90,73 @@ impl Config {
       spreadsheet_id_sessions,
       spreadsheet_id_presenter_institutions,
       spreadsheet_id_strands,
+      store,
+      overrides,
+      settings,
+      verify_after_sync,
+      headshots_dir,
     })
   }
+
+  /// The ID of the presenter custom list, from `PRESENTER_CUSTOM_LIST_ID` or
+  /// else from `bootstrap`.
+  pub fn presenter_list_id(&self) -> miette::Result<u32> {
+    self
+      .configured_or_provisioned_list_id(
+        PRESENTER_LIST_KEY,
+        self.presenter_custom_list_id,
+      )?
+      .ok_or(miette!(
+        "found no presenter custom list: set `PRESENTER_CUSTOM_LIST_ID` or \
+         run `bootstrap`"
+      ))
+  }
+
+  /// The ID of the institutions custom list, from its settings or else from
+  /// `bootstrap`.
+  pub fn institution_list_id(&self) -> miette::Result<u32> {
+    let configured_id = self
+      .settings
+      .institutions
+      .as_ref()
+      .ok_or(miette!("found no institutions list in settings"))?
+      .list_id;
+    self
+      .configured_or_provisioned_list_id(INSTITUTION_LIST_KEY, configured_id)?
+      .ok_or(miette!(
+        "found no institutions custom list: set its `list_id` or run \
+         `bootstrap`"
+      ))
+  }
+
+  /// The ID of a configured custom list, from its settings or else from
+  /// `bootstrap`.
+  pub fn custom_list_id(&self, key: &str) -> miette::Result<u32> {
+    let configured_id = self
+      .settings
+      .custom_lists
+      .get(key)
+      .ok_or(miette!("found no custom list {key:?} in settings"))?
+      .list_id;
+    self
+      .configured_or_provisioned_list_id(key, configured_id)?
+      .ok_or(miette!(
+        "found no ID for custom list {key:?}: set its `list_id` or run \
+         `bootstrap`"
+      ))
+  }
+
+  fn configured_or_provisioned_list_id(
+    &self,
+    key: &str,
+    configured_id: Option<u32>,
+  ) -> miette::Result<Option<u32>> {
+    if configured_id.is_some() {
+      return Ok(configured_id);
+    }
+    self
+      .store
+      .custom_list_id(self.guide_id as u32, key)
+      .context("failed to read custom list ID from store")
+  }
 }
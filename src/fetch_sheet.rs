@@ -36,6 +36,18 @@ impl DecodedSpreadsheet {
       styles: Box::new(styles),
     })
   }
+
+  /// Gets a worksheet that may not exist, returning `None` if it doesn't.
+  pub fn get_optional_worksheet(
+    &mut self,
+    name: &str,
+  ) -> miette::Result<Option<DecodedWorksheet>> {
+    if !self.main.sheet_names().iter().any(|n| n == name) {
+      debug!(name, "found no optional sheet");
+      return Ok(None);
+    }
+    self.get_worksheet(name).map(Some)
+  }
 }
 
 pub struct DecodedWorksheet {
@@ -4,19 +4,43 @@ use miette::{Context, IntoDiagnostic};
 use serde::{Deserialize, Serialize};
 use tracing::{debug, error, instrument, trace};
 
-use self::model::GuidebookScheduleTrack;
-use crate::{HTTP_CLIENT, config::Config};
+use crate::{
+  HTTP_CLIENT,
+  config::Config,
+  store::{Mutation, MutationKind, PendingMutation},
+};
 
 const GUIDEBOOK_BASE_URL: &str = "https://builder.guidebook.com/open-api/v1.1";
 
-#[instrument(skip(config))]
+/// Deletes an entity, journaling `before` as its prior state if it was just
+/// fetched, or fetching it otherwise.
+#[instrument(skip(config, before))]
 pub async fn delete_guidebook_entity(
   config: &Config,
   url: &str,
   id: u32,
+  before: Option<serde_json::Value>,
 ) -> miette::Result<()> {
-  let url =
-    format!("{GUIDEBOOK_BASE_URL}{url}/{id}", url = url.trim_suffix("/"));
+  let endpoint = url.trim_suffix("/");
+  let before = match before {
+    Some(before) => before,
+    None => fetch_guidebook_entity::<serde_json::Value>(config, url, id)
+      .await
+      .context("failed to fetch guidebook entity before deleting it")?,
+  };
+
+  let pending_id = config
+    .store
+    .begin_mutation(&PendingMutation {
+      kind:      MutationKind::Delete,
+      endpoint:  endpoint.to_owned(),
+      entity_id: Some(id),
+      request:   None,
+      before:    Some(before.clone()),
+    })
+    .context("failed to journal pending guidebook entity deletion")?;
+
+  let url = format!("{GUIDEBOOK_BASE_URL}{endpoint}/{id}");
   let req = HTTP_CLIENT
     .delete(url)
     .header(
@@ -30,20 +54,85 @@ pub async fn delete_guidebook_entity(
     .send()
     .await
     .into_diagnostic()
-    .context("failed to send request to delete guidebook entity")?
-    .error_for_status()
-    .into_diagnostic()
-    .context(
+    .context("failed to send request to delete guidebook entity")?;
+  if let Err(e) = resp.error_for_status_ref() {
+    config.store.discard_mutation(pending_id)?;
+    return Err(e).into_diagnostic().context(
       "got server error response from response to delete guidebook entity",
-    )?;
+    );
+  }
   trace!(
     content_length = resp.content_length(),
     "got successful response from entity deletion request"
   );
 
+  config
+    .store
+    .complete_mutation(pending_id, &Mutation {
+      kind:      MutationKind::Delete,
+      endpoint:  endpoint.to_owned(),
+      entity_id: id,
+      request:   None,
+      before:    Some(before),
+      after:     None,
+    })
+    .context(format!(
+      "deleted {endpoint}/{id} in guidebook but failed to journal it, so it's \
+       left pending in the journal"
+    ))?;
+
   Ok(())
 }
 
+#[instrument(skip(config))]
+pub async fn fetch_guidebook_entity<T: for<'a> Deserialize<'a>>(
+  config: &Config,
+  url: &str,
+  id: u32,
+) -> miette::Result<T> {
+  let url =
+    format!("{GUIDEBOOK_BASE_URL}{url}/{id}", url = url.trim_suffix("/"));
+  let req = HTTP_CLIENT
+    .get(url)
+    .header(
+      "Authorization",
+      format!("JWT {api_key}", api_key = config.api_key),
+    )
+    .query(&[("guide", &config.guide_id.to_string())]);
+
+  trace!("sending guidebook request to fetch entity");
+  let resp = req
+    .send()
+    .await
+    .into_diagnostic()
+    .context("failed to send request to fetch guidebook entity")?
+    .error_for_status()
+    .into_diagnostic()
+    .context(
+      "got server error response from response to fetch guidebook entity",
+    )?;
+
+  let payload = resp
+    .text()
+    .await
+    .into_diagnostic()
+    .context("failed to read guidebook entity response body")?;
+
+  let jd = &mut serde_json::Deserializer::from_str(&payload);
+  let payload: T = serde_path_to_error::deserialize(jd)
+    .into_diagnostic()
+    .context("failed to parse guidebook entity response body as type")
+    .inspect_err(|_| {
+      error!(
+        payload,
+        "failed to parse guidebook entity response body as type"
+      );
+    })?;
+  trace!("parsed entity response");
+
+  Ok(payload)
+}
+
 #[instrument(skip(config))]
 async fn fetch_page_of_guidebook_entities<T: for<'a> Deserialize<'a>>(
   config: &Config,
@@ -126,10 +215,132 @@ pub async fn fetch_all_guidebook_entities<T: for<'a> Deserialize<'a>>(
   Ok(results)
 }
 
-#[derive(Clone, Copy, Debug)]
+/// Adds a newly created custom list item to a custom list, which Guidebook
+/// tracks as a relation of its own.
+#[instrument(skip(config))]
+pub async fn relate_guidebook_custom_list_item(
+  config: &Config,
+  list_id: u32,
+  item_id: u32,
+) -> miette::Result<()> {
+  let relation_payload = serde_json::json!({
+    "custom_list": list_id,
+    "custom_list_item": item_id,
+  });
+  upsert_guidebook_entity(
+    config,
+    relation_payload,
+    "/custom-list-item-relations/",
+    Modification::Create,
+  )
+  .await
+  .context("failed to relate custom list item to custom list")?;
+
+  Ok(())
+}
+
+/// Uploads an image to the `image` field of an entity, which Guidebook only
+/// accepts as a multipart form.
+#[instrument(skip(config, image))]
+pub async fn upload_guidebook_image(
+  config: &Config,
+  url: &str,
+  id: u32,
+  file_name: &str,
+  image: Vec<u8>,
+) -> miette::Result<()> {
+  let endpoint = url.trim_suffix("/");
+  let before = fetch_guidebook_entity::<serde_json::Value>(config, url, id)
+    .await
+    .context("failed to fetch guidebook entity before uploading its image")?;
+
+  let mime_type = match file_name.rsplit_once('.').map(|(_, e)| e) {
+    Some(e) if e.eq_ignore_ascii_case("png") => "image/png",
+    Some(e) if e.eq_ignore_ascii_case("gif") => "image/gif",
+    Some(e) if e.eq_ignore_ascii_case("webp") => "image/webp",
+    _ => "image/jpeg",
+  };
+  let part = reqwest::multipart::Part::bytes(image)
+    .file_name(file_name.to_owned())
+    .mime_str(mime_type)
+    .into_diagnostic()
+    .context("failed to build image upload part")?;
+  let form = reqwest::multipart::Form::new()
+    .text("guide", config.guide_id.to_string())
+    .part("image", part);
+
+  let request = serde_json::json!({ "image": file_name });
+  let pending_id = config
+    .store
+    .begin_mutation(&PendingMutation {
+      kind:      MutationKind::Update,
+      endpoint:  endpoint.to_owned(),
+      entity_id: Some(id),
+      request:   Some(request.clone()),
+      before:    Some(before.clone()),
+    })
+    .context("failed to journal pending guidebook image upload")?;
+
+  let url = format!("{GUIDEBOOK_BASE_URL}{endpoint}/{id}");
+  let req = HTTP_CLIENT
+    .patch(url)
+    .header(
+      "Authorization",
+      format!("JWT {api_key}", api_key = config.api_key),
+    )
+    .query(&[("guide", &config.guide_id.to_string())])
+    .multipart(form);
+
+  trace!("sending guidebook request to upload image");
+  let resp = req
+    .send()
+    .await
+    .into_diagnostic()
+    .context("failed to send request to upload guidebook image")?;
+  let server_error = resp
+    .error_for_status_ref()
+    .map(|_| ())
+    .into_diagnostic()
+    .context("got server error response from guidebook image upload request");
+  let payload = resp.text().await.into_diagnostic().context(
+    "failed to consume body of response from guidebook image upload request",
+  )?;
+  if let Err(e) = server_error {
+    error!(payload, "got error response from image upload request");
+    config.store.discard_mutation(pending_id)?;
+    let () = Err(e)?;
+  }
+
+  let after = serde_json::from_str::<serde_json::Value>(&payload)
+    .into_diagnostic()
+    .context("failed to read guidebook image upload response as JSON")?;
+  config
+    .store
+    .complete_mutation(pending_id, &Mutation {
+      kind:      MutationKind::Update,
+      endpoint:  endpoint.to_owned(),
+      entity_id: id,
+      request:   Some(request),
+      before:    Some(before),
+      after:     Some(after),
+    })
+    .context(format!(
+      "uploaded image of {endpoint}/{id} in guidebook but failed to journal \
+       it, so it's left pending in the journal"
+    ))?;
+
+  Ok(())
+}
+
+#[derive(Clone, Debug)]
 pub enum Modification {
   Create,
-  Update { id: u32 },
+  /// Updates an entity, journaling `before` as its prior state if it was
+  /// already fetched, or fetching it otherwise.
+  Update {
+    id:     u32,
+    before: Option<serde_json::Value>,
+  },
 }
 
 #[instrument(skip(config, entity), fields(url))]
@@ -139,17 +350,52 @@ pub async fn upsert_guidebook_entity<T: Serialize + for<'a> Deserialize<'a>>(
   url: &str,
   modification: Modification,
 ) -> miette::Result<T> {
+  let endpoint = url.trim_suffix("/");
+  let before = match &modification {
+    Modification::Create => None,
+    Modification::Update {
+      before: Some(before),
+      ..
+    } => Some(before.clone()),
+    Modification::Update { id, before: None } => Some(
+      fetch_guidebook_entity::<serde_json::Value>(config, url, *id)
+        .await
+        .context("failed to fetch guidebook entity before modifying it")?,
+    ),
+  };
+  let request = serde_json::to_value(&entity)
+    .into_diagnostic()
+    .context("failed to serialize guidebook entity modification request")?;
+
   let url = match modification {
     Modification::Create => format!("{GUIDEBOOK_BASE_URL}{url}"),
-    Modification::Update { id } => {
+    Modification::Update { id, .. } => {
       format!("{GUIDEBOOK_BASE_URL}{url}/{id}", url = url.trim_suffix("/"))
     }
   };
   tracing::Span::current().record("url", &url);
 
+  let kind = match modification {
+    Modification::Create => MutationKind::Create,
+    Modification::Update { .. } => MutationKind::Update,
+  };
+  let pending_id = config
+    .store
+    .begin_mutation(&PendingMutation {
+      kind,
+      endpoint: endpoint.to_owned(),
+      entity_id: match modification {
+        Modification::Create => None,
+        Modification::Update { id, .. } => Some(id),
+      },
+      request: Some(request.clone()),
+      before: before.clone(),
+    })
+    .context("failed to journal pending guidebook entity modification")?;
+
   let req = match modification {
     Modification::Create => HTTP_CLIENT.post(&url),
-    Modification::Update { id: _ } => HTTP_CLIENT.patch(&url),
+    Modification::Update { .. } => HTTP_CLIENT.patch(&url),
   };
   let req = req
     .header(
@@ -189,6 +435,7 @@ pub async fn upsert_guidebook_entity<T: Serialize + for<'a> Deserialize<'a>>(
       payload,
       "got error response from entity modification request"
     );
+    config.store.discard_mutation(pending_id)?;
     let () = Err(e)?;
   }
   trace!(
@@ -196,7 +443,7 @@ pub async fn upsert_guidebook_entity<T: Serialize + for<'a> Deserialize<'a>>(
     "got successful response from entity modification request"
   );
 
-  let payload = serde_json::from_str::<T>(&payload)
+  let after = serde_json::from_str::<serde_json::Value>(&payload)
     .into_diagnostic()
     .context("failed to read guidebook entity modification response as JSON")
     .inspect_err(|_| {
@@ -205,68 +452,37 @@ pub async fn upsert_guidebook_entity<T: Serialize + for<'a> Deserialize<'a>>(
         "failed to deserialize payload of entity modification response"
       );
     })?;
-  trace!("parsed entity modification response");
-
-  Ok(payload)
-}
-
-#[instrument(skip(config, schedule_track), fields(url))]
-pub async fn create_guidebook_schedule_track(
-  config: &Config,
-  schedule_track: GuidebookScheduleTrack,
-) -> miette::Result<GuidebookScheduleTrack> {
-  let url = format!("{GUIDEBOOK_BASE_URL}/schedule-tracks/");
-  tracing::Span::current().record("url", &url);
-
-  let req = HTTP_CLIENT
-    .post(&url)
-    .header(
-      "Authorization",
-      format!("JWT {api_key}", api_key = config.api_key),
-    )
-    .json(&schedule_track);
-
-  trace!("sending guidebook request to create schedule track");
-  let resp = req
-    .send()
-    .await
-    .into_diagnostic()
-    .context("failed to send request to create schedule track")?;
-
-  // extract error before consuming body
-  let server_error = resp
-    .error_for_status_ref()
-    .map(|_| ())
-    .into_diagnostic()
-    .context(
-      "got server error response from guidebook schedule track creation \
-       request",
-    );
-  let content_length = resp.content_length();
-  let payload = resp.text().await.into_diagnostic().context(
-    "failed to consume body of response from guidebook schedule track \
-     creation request",
-  )?;
-
-  // now bubble error
-  if let Err(e) = server_error {
-    error!(
-      payload,
-      "got error response from schedule track creation request"
-    );
-    let () = Err(e)?;
-  }
-  trace!(
-    content_length,
-    "got successful response from schedule track creation request"
-  );
+  let entity_id = match modification {
+    Modification::Create => after["id"].as_u64().ok_or(miette::miette!(
+      "guidebook entity creation response has no ID"
+    ))? as u32,
+    Modification::Update { id, .. } => id,
+  };
+  config
+    .store
+    .complete_mutation(pending_id, &Mutation {
+      kind,
+      endpoint: endpoint.to_owned(),
+      entity_id,
+      request: Some(request),
+      before,
+      after: Some(after),
+    })
+    .context(format!(
+      "applied {kind} of {endpoint}/{entity_id} in guidebook but failed to \
+       journal it, so it's left pending in the journal"
+    ))?;
 
-  let payload = serde_json::from_str::<GuidebookScheduleTrack>(&payload)
+  let payload = serde_json::from_str::<T>(&payload)
     .into_diagnostic()
-    .context(
-      "failed to read guidebook schedule track creation response as JSON",
-    )?;
-  trace!("parsed schedule track creation response");
+    .context("failed to read guidebook entity modification response as JSON")
+    .inspect_err(|_| {
+      error!(
+        payload,
+        "failed to deserialize payload of entity modification response"
+      );
+    })?;
+  trace!("parsed entity modification response");
 
   Ok(payload)
 }
@@ -1,8 +1,8 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use tracing::warn;
+use tracing::{error, info, warn};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GuidebookPagedResult<T> {
@@ -12,21 +12,237 @@ pub struct GuidebookPagedResult<T> {
   pub results:  Vec<T>,
 }
 
-fn patch_field<T: std::fmt::Debug + Clone + PartialEq>(
-  intended: &Option<T>,
-  existing: &Option<T>,
-) -> Option<T> {
-  match (intended, existing) {
-    // not populated, so don't set
-    (None, _) => None,
-    // populated, so set
-    (Some(int), None) => Some(int.clone()),
-    // populated and correct, so don't set
-    (Some(int), Some(exi)) if int == exi => None,
-    // populated and incorrect, so set
-    (Some(int), Some(exi)) => {
-      warn!(intended = ?int, existing = ?exi, "patching incorrect field");
-      Some(int.clone())
+/// A managed entity field, which is either left alone, set to a value, or
+/// cleared.
+///
+/// Missing fields deserialize as `Unmanaged` and `null` deserializes as
+/// `Clear`. `Unmanaged` fields should be skipped when serializing, and
+/// `Clear` serializes as `null`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Field<T> {
+  #[default]
+  Unmanaged,
+  Set(T),
+  Clear,
+}
+
+impl<T> Field<T> {
+  pub fn is_unmanaged(&self) -> bool { matches!(self, Field::Unmanaged) }
+
+  pub fn value(&self) -> Option<&T> {
+    match self {
+      Field::Set(v) => Some(v),
+      _ => None,
+    }
+  }
+}
+
+impl<T> From<Option<T>> for Field<T> {
+  fn from(value: Option<T>) -> Self {
+    match value {
+      Some(v) => Field::Set(v),
+      None => Field::Clear,
+    }
+  }
+}
+
+impl<T: Serialize> Serialize for Field<T> {
+  fn serialize<S: serde::Serializer>(
+    &self,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    match self {
+      Field::Set(v) => serializer.serialize_some(v),
+      Field::Unmanaged | Field::Clear => serializer.serialize_none(),
+    }
+  }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Field<T> {
+  fn deserialize<D: serde::Deserializer<'de>>(
+    deserializer: D,
+  ) -> Result<Self, D::Error> {
+    Option::<T>::deserialize(deserializer).map(Field::from)
+  }
+}
+
+/// Normalizes a field value for comparison, since Guidebook uses empty strings
+/// and lists interchangeably with `null`. Every list field is a set of IDs,
+/// which serializes in no particular order, so lists are sorted.
+fn normalize_field_value(value: serde_json::Value) -> serde_json::Value {
+  match value {
+    serde_json::Value::String(s) if s.is_empty() => serde_json::Value::Null,
+    serde_json::Value::Array(a) if a.is_empty() => serde_json::Value::Null,
+    serde_json::Value::Array(mut a) => {
+      a.sort_by(|x, y| match (x.as_f64(), y.as_f64()) {
+        (Some(x), Some(y)) => x.total_cmp(&y),
+        _ => x.to_string().cmp(&y.to_string()),
+      });
+      serde_json::Value::Array(a)
+    }
+    v => v,
+  }
+}
+
+/// The last-synced values of the managed fields of an entity, keyed by field
+/// name. This is the common ancestor used for three-way merges.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncBase(pub BTreeMap<String, serde_json::Value>);
+
+impl SyncBase {
+  /// Collects the managed fields of an intended entity, i.e. the ones that
+  /// will be serialized.
+  pub fn of<T: Serialize>(entity: &T) -> Self {
+    let mut fields = match serde_json::to_value(entity) {
+      Ok(serde_json::Value::Object(fields)) => fields,
+      _ => serde_json::Map::new(),
+    };
+    for unmanaged in ["id", "guide", "import_id"] {
+      fields.remove(unmanaged);
+    }
+    Self(
+      fields
+        .into_iter()
+        .map(|(k, v)| (k, normalize_field_value(v)))
+        .collect(),
+    )
+  }
+}
+
+/// A managed field whose Guidebook value has diverged from its last-synced
+/// value.
+#[derive(Clone, Debug)]
+pub struct FieldDivergence {
+  pub field:    &'static str,
+  pub base:     serde_json::Value,
+  pub intended: serde_json::Value,
+  pub existing: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MergeReport {
+  /// Fields edited only in Guidebook, which are left alone.
+  pub preserved: Vec<FieldDivergence>,
+  /// Fields edited both in Guidebook and in the spreadsheet, which are left
+  /// alone until someone decides which one wins.
+  pub conflicts: Vec<FieldDivergence>,
+}
+
+impl MergeReport {
+  pub fn log(&self, entity: &str) {
+    for d in &self.preserved {
+      info!(
+        entity,
+        field = d.field,
+        existing = %d.existing,
+        intended = %d.intended,
+        "preserving field edited in guidebook"
+      );
+    }
+    for d in &self.conflicts {
+      error!(
+        entity,
+        field = d.field,
+        base = %d.base,
+        existing = %d.existing,
+        intended = %d.intended,
+        "field was edited in both guidebook and the spreadsheet, leaving it \
+         alone until a human decides"
+      );
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct MergedPatch<T> {
+  pub patch:     T,
+  /// The sync base to record once the patch has been applied.
+  pub next_base: SyncBase,
+  pub report:    MergeReport,
+}
+
+struct ThreeWayMerge<'a> {
+  base:      Option<&'a SyncBase>,
+  next_base: SyncBase,
+  report:    MergeReport,
+}
+
+impl<'a> ThreeWayMerge<'a> {
+  fn new(base: Option<&'a SyncBase>) -> Self {
+    Self {
+      base,
+      next_base: SyncBase::default(),
+      report: MergeReport::default(),
+    }
+  }
+
+  fn field<T: std::fmt::Debug + Clone + PartialEq + Serialize>(
+    &mut self,
+    field: &'static str,
+    intended: &Field<T>,
+    existing: &Field<T>,
+  ) -> Field<T> {
+    // not managed, so don't touch
+    if intended.is_unmanaged() {
+      return Field::Unmanaged;
+    }
+    let intended_value =
+      normalize_field_value(serde_json::to_value(intended).unwrap_or_default());
+    let existing_value =
+      normalize_field_value(serde_json::to_value(existing).unwrap_or_default());
+
+    // populated and correct, so don't set. The typed values are compared too,
+    // since sets compare equal whatever order they serialize in
+    if intended == existing || intended_value == existing_value {
+      self.next_base.0.insert(field.to_owned(), intended_value);
+      return Field::Unmanaged;
+    }
+
+    let Some(base_value) = self
+      .base
+      .and_then(|b| b.0.get(field))
+      .cloned()
+      .map(normalize_field_value)
+    else {
+      // never synced, so the spreadsheet wins
+      warn!(?intended, ?existing, "patching incorrect field");
+      self.next_base.0.insert(field.to_owned(), intended_value);
+      return intended.clone();
+    };
+
+    if existing_value == base_value {
+      // only changed in the spreadsheet, so set or clear
+      warn!(?intended, ?existing, "patching changed field");
+      self.next_base.0.insert(field.to_owned(), intended_value);
+      intended.clone()
+    } else if intended_value == base_value {
+      // only changed in guidebook, so preserve
+      self.report.preserved.push(FieldDivergence {
+        field,
+        base: base_value,
+        intended: intended_value.clone(),
+        existing: existing_value,
+      });
+      self.next_base.0.insert(field.to_owned(), intended_value);
+      Field::Unmanaged
+    } else {
+      // changed in both, so leave it for a human and keep the old base
+      self.report.conflicts.push(FieldDivergence {
+        field,
+        base: base_value.clone(),
+        intended: intended_value,
+        existing: existing_value,
+      });
+      self.next_base.0.insert(field.to_owned(), base_value);
+      Field::Unmanaged
+    }
+  }
+
+  fn finish<T>(self, patch: T) -> MergedPatch<T> {
+    MergedPatch {
+      patch,
+      next_base: self.next_base,
+      report: self.report,
     }
   }
 }
@@ -40,131 +256,192 @@ pub struct GuidebookSession {
   #[serde(rename = "guide")]
   pub guide_id: u32,
   /// The title of your `Session`.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub name: Option<String>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub name: Field<String>,
   /// A text description of the `Session`. This field has a 20,000 character
   /// limit. This field supports basic HTML.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub description_html: Option<String>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub description_html: Field<String>,
   /// The start time of the event. For consistency, all timestamps are
   /// converted to the UTC timezone.
-  pub start_time: DateTime<Utc>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub start_time: Field<DateTime<Utc>>,
   /// The end time of the event. Leave blank for all day events. For
   /// consistency, all timestamps are converted to the UTC timezone.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub end_time: Option<DateTime<Utc>>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub end_time: Field<DateTime<Utc>>,
   /// A boolean value indicating if a `Session` runs for the entire day.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub all_day: Option<bool>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub all_day: Field<bool>,
   /// A boolean value indicating if end-users can rate this `Session`.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub allow_rating: Option<bool>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub allow_rating: Field<bool>,
   /// A boolean value indicating if end-users can add this `Session` to their
   /// personal schedule.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub add_to_schedule: Option<bool>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub add_to_schedule: Field<bool>,
   /// A string field you can use to input your own identifier. This is for
   /// when you have your own IDs for `Session`s in your data store.
   #[serde(skip_serializing_if = "Option::is_none")]
   pub import_id: Option<String>,
   /// Array of IDs of `Location`s this `Session` should belong to.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub locations: Option<HashSet<u32>>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub locations: Field<HashSet<u32>>,
   /// Array of IDs of `ScheduleTracks` this `Session` should belong to.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub schedule_tracks: Option<HashSet<u32>>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub schedule_tracks: Field<HashSet<u32>>,
   /// The order the `Session` will appear.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub rank: Option<f32>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub rank: Field<f32>,
   /// The date from which users can start registering or add the current
   /// session to their personal schedule. Setting this field requires that
   /// `require_login` is true.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub registration_start_date: Option<DateTime<Utc>>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub registration_start_date: Field<DateTime<Utc>>,
   /// The date when users can no longer register or add the current session
   /// to their personal schedule. Setting this field requires that
   /// `require_login` is true.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub registration_end_date: Option<DateTime<Utc>>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub registration_end_date: Field<DateTime<Utc>>,
   /// A boolean value indicating if a user needs to be logged in to add this
   /// `Session` to their schedule. Setting this field requires that
   /// `add_to_schedule` is true.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub require_login: Option<bool>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub require_login: Field<bool>,
   /// A boolean value indicating if this `Session` should have a registration
   /// waitlist. This field requires that `max_capacity` is set.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub waitlist: Option<bool>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub waitlist: Field<bool>,
   /// The number of people who can add this session. Setting this field
   /// requires that `require_login` is true.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub max_capacity: Option<u32>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub max_capacity: Field<u32>,
 }
 
 impl GuidebookSession {
+  /// Checks the prerequisites Guidebook has between the managed registration
+  /// fields, which it would otherwise reject or silently drop.
+  pub fn check_registration_prerequisites(&self) -> miette::Result<()> {
+    let require_login = self.require_login.value().copied().unwrap_or(false);
+    if (self.registration_start_date.value().is_some()
+      || self.registration_end_date.value().is_some())
+      && !require_login
+    {
+      miette::bail!("registration dates require `require_login`");
+    }
+    if require_login && self.add_to_schedule.value() == Some(&false) {
+      miette::bail!("`require_login` requires `add_to_schedule`");
+    }
+    if self.waitlist.value() == Some(&true)
+      && self.max_capacity.value().is_none()
+    {
+      miette::bail!("`waitlist` requires `max_capacity`");
+    }
+    if self.max_capacity.value().is_some() && !require_login {
+      miette::bail!("`max_capacity` requires `require_login`");
+    }
+
+    Ok(())
+  }
+
   pub fn is_empty_patch(&self) -> bool {
-    // not comparing id, guide_id, start_time, or import_id
-    self.name.is_none()
-      && self.description_html.is_none()
-      && self.end_time.is_none()
-      && self.all_day.is_none()
-      && self.allow_rating.is_none()
-      && self.add_to_schedule.is_none()
-      && self.locations.is_none()
-      && self.schedule_tracks.is_none()
-      && self.rank.is_none()
-      && self.registration_start_date.is_none()
-      && self.registration_end_date.is_none()
-      && self.require_login.is_none()
-      && self.waitlist.is_none()
-      && self.max_capacity.is_none()
-  }
-
-  pub fn generate_patch_diff(intended: &Self, existing: &Self) -> Self {
-    Self {
+    // not comparing id, guide_id, or import_id
+    self.name.is_unmanaged()
+      && self.description_html.is_unmanaged()
+      && self.start_time.is_unmanaged()
+      && self.end_time.is_unmanaged()
+      && self.all_day.is_unmanaged()
+      && self.allow_rating.is_unmanaged()
+      && self.add_to_schedule.is_unmanaged()
+      && self.locations.is_unmanaged()
+      && self.schedule_tracks.is_unmanaged()
+      && self.rank.is_unmanaged()
+      && self.registration_start_date.is_unmanaged()
+      && self.registration_end_date.is_unmanaged()
+      && self.require_login.is_unmanaged()
+      && self.waitlist.is_unmanaged()
+      && self.max_capacity.is_unmanaged()
+  }
+
+  /// Generates a patch using a three-way merge against the last-synced
+  /// values in `base`, if there are any.
+  pub fn generate_patch_diff(
+    intended: &Self,
+    existing: &Self,
+    base: Option<&SyncBase>,
+  ) -> MergedPatch<Self> {
+    let mut merge = ThreeWayMerge::new(base);
+    let patch = Self {
       // ID can't be updated, and the difference here isn't meaningful
       id: None,
       // guide_id can't be updated, and shouldn't
       guide_id: existing.guide_id,
-      name: patch_field(&intended.name.clone(), &existing.name.clone()),
-      description_html: patch_field(
+      name: merge.field("name", &intended.name, &existing.name),
+      description_html: merge.field(
+        "description_html",
         &intended.description_html,
         &existing.description_html,
       ),
-      start_time: intended.start_time,
-      end_time: patch_field(&intended.end_time, &existing.end_time),
-      all_day: patch_field(&intended.all_day, &existing.all_day),
-      allow_rating: patch_field(&intended.allow_rating, &existing.allow_rating),
-      add_to_schedule: patch_field(
+      start_time: merge.field(
+        "start_time",
+        &intended.start_time,
+        &existing.start_time,
+      ),
+      end_time: merge.field("end_time", &intended.end_time, &existing.end_time),
+      all_day: merge.field("all_day", &intended.all_day, &existing.all_day),
+      allow_rating: merge.field(
+        "allow_rating",
+        &intended.allow_rating,
+        &existing.allow_rating,
+      ),
+      add_to_schedule: merge.field(
+        "add_to_schedule",
         &intended.add_to_schedule,
         &existing.add_to_schedule,
       ),
       // guide_id can't be updated, and shouldn't
       import_id: existing.import_id.clone(),
-      locations: patch_field(&intended.locations, &existing.locations),
-      schedule_tracks: patch_field(
+      locations: merge.field(
+        "locations",
+        &intended.locations,
+        &existing.locations,
+      ),
+      schedule_tracks: merge.field(
+        "schedule_tracks",
         &intended.schedule_tracks,
         &existing.schedule_tracks,
       ),
-      rank: patch_field(&intended.rank, &existing.rank),
-      registration_start_date: patch_field(
+      rank: merge.field("rank", &intended.rank, &existing.rank),
+      registration_start_date: merge.field(
+        "registration_start_date",
         &intended.registration_start_date,
         &existing.registration_start_date,
       ),
-      registration_end_date: patch_field(
+      registration_end_date: merge.field(
+        "registration_end_date",
         &intended.registration_end_date,
         &existing.registration_end_date,
       ),
-      require_login: patch_field(
+      require_login: merge.field(
+        "require_login",
         &intended.require_login,
         &existing.require_login,
       ),
-      waitlist: patch_field(&intended.waitlist, &existing.waitlist),
-      max_capacity: patch_field(&intended.max_capacity, &existing.max_capacity),
-    }
+      waitlist: merge.field("waitlist", &intended.waitlist, &existing.waitlist),
+      max_capacity: merge.field(
+        "max_capacity",
+        &intended.max_capacity,
+        &existing.max_capacity,
+      ),
+    };
+    merge.finish(patch)
   }
 }
 
+/// Presenters are custom list items, so the items of other custom lists share
+/// their model.
+pub type GuidebookCustomListItem = GuidebookPresenter;
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct GuidebookPresenter {
   /// The ID of the `Presenter`
@@ -174,66 +451,89 @@ pub struct GuidebookPresenter {
   #[serde(rename = "guide")]
   pub guide_id:         u32,
   /// The title of your `Presenter`.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub name:             Option<String>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub name:             Field<String>,
   /// A text description of the `Session`. This field has a 20,000 character
   /// limit. This field supports basic HTML.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub description_html: Option<String>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub description_html: Field<String>,
   /// A short tagline thats displayed below the name of the name field.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub subtitle:         Option<String>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub subtitle:         Field<String>,
   /// A boolean value indicating if end-users can rate this `Session`.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub allow_rating:     Option<bool>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub allow_rating:     Field<bool>,
   /// A string field you can use to input your own identifier. This is for
   /// when you have your own IDs for `Session`s in your data store.
   #[serde(skip_serializing_if = "Option::is_none")]
   pub import_id:        Option<String>,
   /// Array of IDs of `Location`s this `Session` should belong to.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub locations:        Option<HashSet<u32>>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub locations:        Field<HashSet<u32>>,
   /// An email for the item that users will be able to contact directly from
   /// the app or Guidebook Web.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub contact_email:    Option<String>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub contact_email:    Field<String>,
 }
 
 impl GuidebookPresenter {
   pub fn is_empty_patch(&self) -> bool {
-    // not comparing id, guide_id, description_html, or import_id
-    self.name.is_none()
-      && self.description_html.as_ref().is_none_or(String::is_empty)
-      && self.subtitle.as_ref().is_none_or(String::is_empty)
-      && self.allow_rating.is_none()
-      && self.locations.is_none()
-      && self.contact_email.is_none()
+    // not comparing id or guide_id
+    self.name.is_unmanaged()
+      && self.description_html.is_unmanaged()
+      && self.subtitle.is_unmanaged()
+      && self.allow_rating.is_unmanaged()
+      && self.locations.is_unmanaged()
+      && self.contact_email.is_unmanaged()
+      && self.import_id.is_none()
   }
 
-  pub fn generate_patch_diff(intended: &Self, existing: &Self) -> Self {
-    Self {
+  /// Generates a patch using a three-way merge against the last-synced
+  /// values in `base`, if there are any.
+  pub fn generate_patch_diff(
+    intended: &Self,
+    existing: &Self,
+    base: Option<&SyncBase>,
+  ) -> MergedPatch<Self> {
+    let mut merge = ThreeWayMerge::new(base);
+    let patch = Self {
       // ID can't be updated, and the difference here isn't meaningful
       id:               existing.id,
       // guide_id can't be updated, and shouldn't
       guide_id:         existing.guide_id,
-      name:             patch_field(
-        &intended.name.clone(),
-        &existing.name.clone(),
+      name:             merge.field("name", &intended.name, &existing.name),
+      description_html: merge.field(
+        "description_html",
+        &intended.description_html,
+        &existing.description_html,
       ),
-      description_html: intended.description_html.clone(),
-      subtitle:         patch_field(&intended.subtitle, &existing.subtitle),
-      allow_rating:     patch_field(
+      subtitle:         merge.field(
+        "subtitle",
+        &intended.subtitle,
+        &existing.subtitle,
+      ),
+      allow_rating:     merge.field(
+        "allow_rating",
         &intended.allow_rating,
         &existing.allow_rating,
       ),
-      // guide_id can't be updated, and shouldn't
-      import_id:        existing.import_id.clone(),
-      locations:        patch_field(&intended.locations, &existing.locations),
-      contact_email:    patch_field(
+      // only set when adopting an item that was matched by name
+      import_id:        intended
+        .import_id
+        .clone()
+        .filter(|iid| existing.import_id.as_ref() != Some(iid)),
+      locations:        merge.field(
+        "locations",
+        &intended.locations,
+        &existing.locations,
+      ),
+      contact_email:    merge.field(
+        "contact_email",
         &intended.contact_email,
         &existing.contact_email,
       ),
-    }
+    };
+    merge.finish(patch)
   }
 }
 
@@ -246,22 +546,66 @@ pub struct GuidebookScheduleTrack {
   #[serde(rename = "guide")]
   pub guide_id:         u32,
   /// The title of your `Session`.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub name:             Option<String>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub name:             Field<String>,
   /// A text description of the `Session`. This field has a 20,000 character
   /// limit. This field supports basic HTML.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub description_html: Option<String>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub description_html: Field<String>,
   /// Hex value of the color you want this track to be. Used for highlighting
   /// sessions in the app. Example: “#000080” for blue.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub color:            Option<String>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub color:            Field<String>,
+  /// The order the `ScheduleTrack` will appear.
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub rank:             Field<f32>,
   /// A string field you can use to input your own identifier. This is for
   /// when you have your own IDs for `Session`s in your data store.
   #[serde(skip_serializing_if = "Option::is_none")]
   pub import_id:        Option<String>,
 }
 
+impl GuidebookScheduleTrack {
+  pub fn is_empty_patch(&self) -> bool {
+    // not comparing id or guide_id
+    self.name.is_unmanaged()
+      && self.description_html.is_unmanaged()
+      && self.color.is_unmanaged()
+      && self.rank.is_unmanaged()
+      && self.import_id.is_none()
+  }
+
+  /// Generates a patch using a three-way merge against the last-synced
+  /// values in `base`, if there are any.
+  pub fn generate_patch_diff(
+    intended: &Self,
+    existing: &Self,
+    base: Option<&SyncBase>,
+  ) -> MergedPatch<Self> {
+    let mut merge = ThreeWayMerge::new(base);
+    let patch = Self {
+      // ID can't be updated, and the difference here isn't meaningful
+      id:               None,
+      // guide_id can't be updated, and shouldn't
+      guide_id:         existing.guide_id,
+      name:             merge.field("name", &intended.name, &existing.name),
+      description_html: merge.field(
+        "description_html",
+        &intended.description_html,
+        &existing.description_html,
+      ),
+      color:            merge.field("color", &intended.color, &existing.color),
+      rank:             merge.field("rank", &intended.rank, &existing.rank),
+      // only set when adopting a track that was matched by name
+      import_id:        intended
+        .import_id
+        .clone()
+        .filter(|iid| existing.import_id.as_ref() != Some(iid)),
+    };
+    merge.finish(patch)
+  }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct GuidebookLocation {
   /// The ID of the `Location`
@@ -271,20 +615,305 @@ pub struct GuidebookLocation {
   #[serde(rename = "guide")]
   pub guide_id:      u32,
   /// The name of your `Location`.
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub name:          Option<String>,
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub name:          Field<String>,
   /// A string field you can use to input your own identifier. This is for
   /// when you have your own IDs for `Location`s in your data store.
   #[serde(skip_serializing_if = "Option::is_none")]
   pub import_id:     Option<String>,
   /// Either 1, 2 or 3. 1 is the special Main Venue location. 2 is a
   /// Placeholder location. 3 is a “Google Maps Location”
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub location_type: Field<u32>,
+  /// Latitude of this Location - only required if this Location is of type
+  /// (“Google Maps Location”).
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub latitude:      Field<f64>,
+  /// Longitude of this Location - only required if this Location is of type
+  /// (“Google Maps Location”).
+  #[serde(default, skip_serializing_if = "Field::is_unmanaged")]
+  pub longitude:     Field<f64>,
+}
+
+impl GuidebookLocation {
+  pub fn is_empty_patch(&self) -> bool {
+    // not comparing id or guide_id
+    self.name.is_unmanaged()
+      && self.location_type.is_unmanaged()
+      && self.latitude.is_unmanaged()
+      && self.longitude.is_unmanaged()
+      && self.import_id.is_none()
+  }
+
+  /// Generates a patch using a three-way merge against the last-synced
+  /// values in `base`, if there are any.
+  pub fn generate_patch_diff(
+    intended: &Self,
+    existing: &Self,
+    base: Option<&SyncBase>,
+  ) -> MergedPatch<Self> {
+    let mut merge = ThreeWayMerge::new(base);
+    let patch = Self {
+      // ID can't be updated, and the difference here isn't meaningful
+      id:            None,
+      // guide_id can't be updated, and shouldn't
+      guide_id:      existing.guide_id,
+      name:          merge.field("name", &intended.name, &existing.name),
+      // only set when adopting a location that was matched by name
+      import_id:     intended
+        .import_id
+        .clone()
+        .filter(|iid| existing.import_id.as_ref() != Some(iid)),
+      location_type: merge.field(
+        "location_type",
+        &intended.location_type,
+        &existing.location_type,
+      ),
+      latitude:      merge.field(
+        "latitude",
+        &intended.latitude,
+        &existing.latitude,
+      ),
+      longitude:     merge.field(
+        "longitude",
+        &intended.longitude,
+        &existing.longitude,
+      ),
+    };
+    merge.finish(patch)
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GuidebookContentType {
+  #[serde(rename = "schedule.session")]
+  Session,
+  #[serde(rename = "custom_list.customlistitem")]
+  CustomListItem,
+  /// Any content type this tool doesn't manage, e.g. maps, PDFs or sponsors.
+  #[serde(other)]
+  Other,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GuidebookLink {
+  /// The ID of the `Link`
   #[serde(skip_serializing_if = "Option::is_none")]
-  pub location_type: Option<u32>,
-  // /// Latitude of this Location - only required if this Location is of type
-  // /// (“Google Maps Location”).
-  // pub latitude:      Option<f32>,
-  // /// Longitude of this Location - only required if this Location is of type
-  // /// (“Google Maps Location”).
-  // pub longitude:     Option<f32>,
+  pub id:                  Option<u32>,
+  /// The specific `Guide` your `Link` belongs to.
+  #[serde(rename = "guide")]
+  pub guide_id:            u32,
+  /// The ID of the object the `Link` starts from.
+  pub source_object_id:    u32,
+  /// The content type of the object the `Link` starts from.
+  pub source_content_type: GuidebookContentType,
+  /// The ID of the object the `Link` points to.
+  pub target_object_id:    u32,
+  /// The content type of the object the `Link` points to.
+  pub target_content_type: GuidebookContentType,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GuidebookGuide {
+  /// The ID of the `Guide`
+  pub id:   u32,
+  /// The name of your `Guide`.
+  pub name: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GuidebookCustomList {
+  /// The ID of the `CustomList`
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub id:       Option<u32>,
+  /// The specific `Guide` your `CustomList` belongs to.
+  #[serde(rename = "guide")]
+  pub guide_id: u32,
+  /// The name of your `CustomList`, shown in the app menu.
+  pub name:     String,
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn base(fields: serde_json::Value) -> SyncBase {
+    serde_json::from_value(fields).unwrap()
+  }
+
+  fn session(fields: serde_json::Value) -> GuidebookSession {
+    let mut body = json!({ "guide": 1, "start_time": "2026-03-05T14:00:00Z" });
+    body
+      .as_object_mut()
+      .unwrap()
+      .extend(fields.as_object().unwrap().clone());
+    serde_json::from_value(body).unwrap()
+  }
+
+  #[test]
+  fn normalizes_empty_values_to_null() {
+    assert_eq!(normalize_field_value(json!("")), json!(null));
+    assert_eq!(normalize_field_value(json!([])), json!(null));
+    assert_eq!(normalize_field_value(json!("Lunch")), json!("Lunch"));
+    assert_eq!(normalize_field_value(json!(false)), json!(false));
+  }
+
+  #[test]
+  fn normalizes_sets_into_sorted_order() {
+    assert_eq!(normalize_field_value(json!([3, 1, 2])), json!([1, 2, 3]));
+  }
+
+  #[test]
+  fn sync_base_stores_sets_in_sorted_order() {
+    let intended =
+      session(json!({ "locations": [5, 3, 9, 1], "schedule_tracks": [] }));
+
+    let base = SyncBase::of(&intended);
+
+    assert_eq!(base.0["locations"], json!([1, 3, 5, 9]));
+    assert_eq!(base.0["schedule_tracks"], json!(null));
+  }
+
+  #[test]
+  fn pushes_field_changed_only_in_spreadsheet() {
+    let base = base(json!({ "name": "Keynote" }));
+    let mut merge = ThreeWayMerge::new(Some(&base));
+
+    let patched = merge.field(
+      "name",
+      &Field::Set("Opening Keynote".to_owned()),
+      &Field::Set("Keynote".to_owned()),
+    );
+
+    assert_eq!(patched, Field::Set("Opening Keynote".to_owned()));
+    assert_eq!(merge.next_base.0["name"], json!("Opening Keynote"));
+    assert!(merge.report.preserved.is_empty());
+    assert!(merge.report.conflicts.is_empty());
+  }
+
+  #[test]
+  fn keeps_field_changed_only_in_guidebook() {
+    let base = base(json!({ "name": "Keynote" }));
+    let mut merge = ThreeWayMerge::new(Some(&base));
+
+    let patched = merge.field(
+      "name",
+      &Field::Set("Keynote".to_owned()),
+      &Field::Set("Keynote Address".to_owned()),
+    );
+
+    assert_eq!(patched, Field::Unmanaged);
+    assert_eq!(merge.next_base.0["name"], json!("Keynote"));
+    assert_eq!(merge.report.preserved.len(), 1);
+    assert!(merge.report.conflicts.is_empty());
+  }
+
+  #[test]
+  fn reports_field_changed_in_both() {
+    let base = base(json!({ "name": "Keynote" }));
+    let mut merge = ThreeWayMerge::new(Some(&base));
+
+    let patched = merge.field(
+      "name",
+      &Field::Set("Opening Keynote".to_owned()),
+      &Field::Set("Keynote Address".to_owned()),
+    );
+
+    assert_eq!(patched, Field::Unmanaged);
+    assert_eq!(merge.next_base.0["name"], json!("Keynote"));
+    assert!(merge.report.preserved.is_empty());
+    assert_eq!(merge.report.conflicts.len(), 1);
+  }
+
+  #[test]
+  fn patches_field_never_synced() {
+    let mut merge = ThreeWayMerge::new(None);
+
+    let patched = merge.field(
+      "name",
+      &Field::Set("Keynote".to_owned()),
+      &Field::Set("Welcome".to_owned()),
+    );
+
+    assert_eq!(patched, Field::Set("Keynote".to_owned()));
+    assert_eq!(merge.next_base.0["name"], json!("Keynote"));
+  }
+
+  #[test]
+  fn leaves_unmanaged_field_alone() {
+    let mut merge = ThreeWayMerge::new(None);
+
+    let patched = merge.field(
+      "name",
+      &Field::<String>::Unmanaged,
+      &Field::Set("Welcome".to_owned()),
+    );
+
+    assert_eq!(patched, Field::Unmanaged);
+    assert!(merge.next_base.0.is_empty());
+  }
+
+  #[test]
+  fn leaves_equal_sets_alone() {
+    let ids = (1..=64).collect::<Vec<u32>>();
+    let intended = ids.iter().copied().collect::<HashSet<_>>();
+    let existing = ids.iter().rev().copied().collect::<HashSet<_>>();
+    let base = base(json!({ "locations": ids }));
+    let mut merge = ThreeWayMerge::new(Some(&base));
+
+    let patched =
+      merge.field("locations", &Field::Set(intended), &Field::Set(existing));
+
+    assert_eq!(patched, Field::Unmanaged);
+    assert!(merge.report.preserved.is_empty());
+    assert!(merge.report.conflicts.is_empty());
+    assert_eq!(merge.next_base.0["locations"], json!(ids));
+  }
+
+  #[test]
+  fn pushes_set_changed_in_spreadsheet_against_unsorted_base() {
+    let base = base(json!({ "locations": [9, 3, 5] }));
+    let mut merge = ThreeWayMerge::new(Some(&base));
+
+    let patched = merge.field(
+      "locations",
+      &Field::Set(HashSet::from([3, 5, 9, 12])),
+      &Field::Set(HashSet::from([5, 9, 3])),
+    );
+
+    assert_eq!(patched, Field::Set(HashSet::from([3, 5, 9, 12])));
+    assert!(merge.report.conflicts.is_empty());
+  }
+
+  #[test]
+  fn keeps_start_time_moved_in_guidebook() {
+    let intended = session(json!({ "name": "Keynote" }));
+    let existing = session(json!({
+      "name": "Keynote Address",
+      "start_time": "2026-03-05T15:00:00Z",
+    }));
+    let base = SyncBase::of(&session(json!({ "name": "Keynote" })));
+
+    let merged =
+      GuidebookSession::generate_patch_diff(&intended, &existing, Some(&base));
+
+    assert_eq!(merged.patch.start_time, Field::Unmanaged);
+    assert_eq!(merged.patch.name, Field::Unmanaged);
+    assert!(merged.patch.is_empty_patch());
+    assert_eq!(merged.report.preserved.len(), 2);
+  }
+
+  #[test]
+  fn pushes_start_time_moved_in_spreadsheet() {
+    let intended = session(json!({ "start_time": "2026-03-05T16:00:00Z" }));
+    let existing = session(json!({}));
+    let base = SyncBase::of(&existing);
+
+    let merged =
+      GuidebookSession::generate_patch_diff(&intended, &existing, Some(&base));
+
+    assert_eq!(merged.patch.start_time, intended.start_time);
+  }
 }
@@ -2,24 +2,34 @@
 #![feature(int_roundings)]
 #![feature(trim_prefix_suffix)]
 #![feature(pattern)]
-#![feature(iter_intersperse)]
 
+mod bootstrap;
 mod config;
 mod fetch_sheet;
 mod guidebook;
 mod nasup_to_guidebook;
+mod overrides;
 mod parse_nasup;
+mod reconcile_guidebook_custom_lists;
 mod reconcile_guidebook_locations;
 mod reconcile_guidebook_presenters;
 mod reconcile_guidebook_sessions;
 mod reconcile_guidebook_strands;
+mod render_description;
+mod rollback;
+mod settings;
 mod state;
+mod store;
+mod synchronize_headshots;
 mod synchronize_links;
 mod synth_nasup;
+mod templates;
+mod track_colors;
+mod verify_convergence;
 
 use std::sync::LazyLock;
 
-use miette::Context;
+use miette::{Context, IntoDiagnostic};
 use tracing::info;
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
@@ -28,6 +38,32 @@ use self::{config::Config, state::MasterState};
 static HTTP_CLIENT: LazyLock<reqwest::Client> =
   LazyLock::new(reqwest::Client::new);
 
+#[derive(Debug)]
+enum Command {
+  Sync,
+  Bootstrap,
+  Rollback { run_id: i64 },
+}
+
+impl Command {
+  fn from_args() -> miette::Result<Self> {
+    let mut args = std::env::args().skip(1);
+    Ok(match args.next().as_deref() {
+      None | Some("sync") => Command::Sync,
+      Some("bootstrap") => Command::Bootstrap,
+      Some("rollback") => Command::Rollback {
+        run_id: args
+          .next()
+          .ok_or(miette::miette!("missing run ID: usage: rollback <run-id>"))?
+          .parse()
+          .into_diagnostic()
+          .context("failed to parse run ID")?,
+      },
+      Some(c) => miette::bail!("unknown command: {c:?}"),
+    })
+  }
+}
+
 #[tokio::main]
 async fn main() -> miette::Result<()> {
   tracing_subscriber::registry()
@@ -35,8 +71,30 @@ async fn main() -> miette::Result<()> {
     .with(EnvFilter::from_default_env())
     .init();
 
+  let command = Command::from_args().context("failed to parse arguments")?;
   let config =
     Config::from_env().context("failed to gather config from env")?;
+  let run_id = config
+    .store
+    .begin_run(&format!("{command:?}"))
+    .context("failed to begin run")?;
+  info!(run_id, ?command, "began run");
+
+  match command {
+    Command::Sync => sync(&config).await,
+    Command::Bootstrap => bootstrap::bootstrap(&config)
+      .await
+      .context("failed to bootstrap guide"),
+    Command::Rollback { run_id } => rollback::rollback_run(&config, run_id)
+      .await
+      .context("failed to roll back run"),
+  }
+}
+
+async fn sync(config: &Config) -> miette::Result<()> {
+  bootstrap::check_recorded_custom_lists(config)
+    .await
+    .context("failed to check custom lists recorded by bootstrap")?;
 
   // drive state machine
   let mut state = MasterState::Start;
@@ -47,7 +105,7 @@ async fn main() -> miette::Result<()> {
         break;
       }
       s => {
-        state = s.step(&config).await.context("failed to step state")?;
+        state = s.step(config).await.context("failed to step state")?;
       }
     }
   }
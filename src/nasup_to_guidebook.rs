@@ -1,95 +1,387 @@
 use std::{
-  collections::HashSet,
-  hash::{DefaultHasher, Hash, Hasher},
+  cmp::Reverse,
+  collections::{BTreeMap, BTreeSet, HashMap, HashSet},
 };
 
+use chrono_tz::US::Eastern;
+use miette::Context;
 use tracing::{debug, instrument, warn};
 
 use crate::{
-  config::Config,
+  config::{Config, INSTITUTION_LIST_KEY},
   guidebook::model::{
-    GuidebookLocation, GuidebookPresenter, GuidebookScheduleTrack,
-    GuidebookSession,
+    Field, GuidebookCustomListItem, GuidebookLocation, GuidebookPresenter,
+    GuidebookScheduleTrack, GuidebookSession,
+  },
+  parse_nasup::parse_model::{
+    PairedConcurrentDiscriminant, ParsedNasupCustomListItem,
+    ParsedNasupLocation, ParsedNasupRichText, ParsedNasupSessionType,
+  },
+  render_description::{
+    DESCRIPTION_HTML_LIMIT, limit_description_html, render_description_html,
+    render_limited_description_html,
+  },
+  settings::{
+    CustomListSettings, InstitutionListSettings, LocationType, RoomSettings,
+    Settings,
+  },
+  synth_nasup::{NasupAgendaItem, NasupPresenter, NasupSession},
+  track_colors::{
+    TrackColorFamily, TrackColorRequest, assign_track_colors,
+    check_track_colors,
   },
-  parse_nasup::parse_model::ParsedNasupSessionType,
-  synth_nasup::{NasupPresenter, NasupSession},
 };
 
+/// Where a schedule track comes from, which namespaces its import ID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScheduleTrackSource {
+  Strand,
+  SessionType,
+  Audience,
+  Agenda,
+}
+
+impl ScheduleTrackSource {
+  /// The import ID of the track with the given source name. This stays the
+  /// same when the track is renamed through the overrides file.
+  pub fn import_id(&self, name: &str) -> String {
+    match self {
+      ScheduleTrackSource::Strand => format!("strand:{name}"),
+      ScheduleTrackSource::SessionType => format!("session-type:{name}"),
+      ScheduleTrackSource::Audience => format!("audience:{name}"),
+      ScheduleTrackSource::Agenda => format!("agenda:{name}"),
+    }
+  }
+
+  fn color_family(&self) -> TrackColorFamily {
+    match self {
+      ScheduleTrackSource::Strand => TrackColorFamily::Vivid,
+      ScheduleTrackSource::SessionType | ScheduleTrackSource::Agenda => {
+        TrackColorFamily::Muted
+      }
+      ScheduleTrackSource::Audience => TrackColorFamily::Pastel,
+    }
+  }
+}
+
 pub fn nasup_sessions_to_guidebook_schedule_tracks(
   config: &Config,
   nasup_sessions: &[NasupSession],
+  agenda_items: &[NasupAgendaItem],
 ) -> miette::Result<Vec<GuidebookScheduleTrack>> {
   let strands = nasup_sessions
     .iter()
-    .filter_map(|s| s.strand.clone())
-    .collect::<HashSet<_>>();
+    .flat_map(|s| {
+      s.strands.iter().map(|n| {
+        let metadata = s.strand_metadata.iter().find(|m| &m.strand == n);
+        (n.clone(), metadata.cloned())
+      })
+    })
+    .collect::<HashMap<_, _>>();
   let types = nasup_sessions
     .iter()
     .map(|s| s.session_type.clone())
     .filter(ParsedNasupSessionType::included)
     .map(|t| t.to_string())
     .collect::<HashSet<_>>();
+  let audiences = nasup_sessions
+    .iter()
+    .filter(|_| config.settings.audience_tracks)
+    .flat_map(|s| s.intended_audience.clone())
+    .collect::<HashSet<_>>();
 
-  Ok(
-    strands
-      .into_iter()
-      .map(|n| (true, n))
-      .chain(types.into_iter().map(|n| (false, n)))
-      .map(|(s, n)| GuidebookScheduleTrack {
-        id:               None,
-        guide_id:         config.guide_id as u32,
-        name:             Some(n.clone()),
-        description_html: None,
-        color:            Some(hex_color_from_name(&n, s)),
-        import_id:        None,
+  let tracks = strands
+    .into_iter()
+    .map(|(n, m)| (ScheduleTrackSource::Strand, n, m))
+    .chain(
+      types
+        .into_iter()
+        .map(|n| (ScheduleTrackSource::SessionType, n, None)),
+    )
+    .chain(
+      audiences
+        .into_iter()
+        .map(|n| (ScheduleTrackSource::Audience, n, None)),
+    )
+    .chain(
+      config
+        .settings
+        .agenda
+        .as_ref()
+        .filter(|_| !agenda_items.is_empty())
+        .map(|a| (ScheduleTrackSource::Agenda, a.track_name.clone(), None)),
+    )
+    .collect::<Vec<_>>();
+  let colors = assign_track_colors(
+    &config.settings.track_colors,
+    &tracks
+      .iter()
+      .map(|(source, n, metadata)| TrackColorRequest {
+        name:     n.clone(),
+        family:   source.color_family(),
+        explicit: metadata.as_ref().and_then(|m| m.color.clone()),
       })
-      .collect(),
-  )
+      .collect::<Vec<_>>(),
+  );
+
+  let schedule_tracks = tracks
+    .into_iter()
+    .zip(colors)
+    .map(|((source, n, metadata), color)| GuidebookScheduleTrack {
+      id:               None,
+      guide_id:         config.guide_id as u32,
+      name:             Field::Set(n.clone()),
+      // tracks without a metadata row leave descriptions to the builder
+      description_html: metadata.as_ref().map_or(Field::Unmanaged, |m| {
+        m.description
+          .as_ref()
+          .map(|d| {
+            let html = render_description_html(&ParsedNasupRichText::plain(d));
+            limit_description_html(html, DESCRIPTION_HTML_LIMIT, &n)
+          })
+          .into()
+      }),
+      color:            Field::Set(color),
+      rank:             metadata
+        .as_ref()
+        .and_then(|m| m.order)
+        .map_or(Field::Unmanaged, Field::Set),
+      import_id:        Some(source.import_id(&n)),
+    })
+    .map(|st| config.overrides.apply_to_schedule_track(st))
+    .try_collect::<Vec<_>>()?;
+
+  check_track_colors(
+    &config.settings.track_colors,
+    &schedule_tracks
+      .iter()
+      .filter_map(|st| {
+        Some((st.name.value()?.clone(), st.color.value()?.clone()))
+      })
+      .collect::<Vec<_>>(),
+  );
+
+  Ok(schedule_tracks)
 }
 
-fn hex_color_from_name(name: &str, dark: bool) -> String {
-  let mut hasher = DefaultHasher::new();
-  name.hash(&mut hasher);
-  let name_hash = hasher.finish();
-  let hue = name_hash as f64 / (u64::MAX as f64 / 360.0);
-  let color = colorutils_rs::Oklch {
-    l: 0.7,
-    c: if dark { 0.16 } else { 0.05 },
-    h: hue as f32,
-  }
-  .to_rgb(colorutils_rs::TransferFunction::Srgb);
-  format!(
-    "#{r:02X}{g:02X}{b:02X}",
-    r = color.r,
-    g = color.g,
-    b = color.b
-  )
+/// The import ID of the location for the room with the given canonical name.
+/// This stays the same when the room is renamed through settings.
+pub fn location_import_id(room_name: &str) -> String {
+  format!("room:{room_name}")
 }
 
 pub fn nasup_sessions_to_guidebook_locations(
   config: &Config,
   nasup_sessions: &[NasupSession],
+  agenda_items: &[NasupAgendaItem],
 ) -> miette::Result<Vec<GuidebookLocation>> {
-  Ok(
-    nasup_sessions
-      .iter()
-      .map(|s| s.room.clone())
-      .collect::<HashSet<_>>()
-      .into_iter()
-      .map(|l| GuidebookLocation {
+  let default_room = RoomSettings::default();
+  nasup_sessions
+    .iter()
+    .flat_map(|s| &s.rooms)
+    .chain(agenda_items.iter().flat_map(|a| &a.rooms))
+    .map(|r| config.settings.canonical_room_name(&r.name))
+    .collect::<HashSet<_>>()
+    .into_iter()
+    .map(|room_name| {
+      let room = config.settings.resolve_room(&room_name).map(|(_, r)| r);
+      if room.is_none() && !config.settings.rooms.is_empty() {
+        warn!(room = room_name, "found room that is not in room registry");
+      }
+      let room = room.unwrap_or(&default_room);
+      debug!(
+        room = room_name,
+        building = room.building,
+        floor = room.floor,
+        capacity = room.capacity,
+        "resolved room"
+      );
+
+      let coordinate = |c: Option<f64>| {
+        if room.location_type == LocationType::GoogleMaps {
+          c.into()
+        } else {
+          Field::Unmanaged
+        }
+      };
+      GuidebookLocation {
         id:            None,
         guide_id:      config.guide_id as u32,
-        name:          Some(l.name),
-        import_id:     None,
-        location_type: Some(2),
-      })
-      .collect(),
-  )
+        name:          Field::Set(room_display_name(
+          &config.settings,
+          &room_name,
+        )),
+        import_id:     Some(location_import_id(&room_name)),
+        location_type: Field::Set(room.location_type.guidebook_id()),
+        latitude:      coordinate(room.latitude),
+        longitude:     coordinate(room.longitude),
+      }
+    })
+    .map(|l| config.overrides.apply_to_location(l))
+    .try_collect()
+}
+
+/// The name a room is shown by in Guidebook. The building and floor have no
+/// location fields of their own, so they're shown after the name.
+fn room_display_name(settings: &Settings, room_name: &str) -> String {
+  let Some((_, room)) = settings.resolve_room(room_name) else {
+    return room_name.to_owned();
+  };
+  let display_name = room.name.clone().unwrap_or(room_name.to_owned());
+  let place = room
+    .building
+    .iter()
+    .cloned()
+    .chain(room.floor.as_ref().map(|f| format!("Floor {f}")))
+    .collect::<Vec<_>>();
+  match place.as_slice() {
+    [] => display_name,
+    place => format!("{display_name} ({})", place.join(", ")),
+  }
 }
 
 #[derive(Clone, Debug)]
 pub struct WithLinks<T>(pub T, pub Vec<u32>);
 
+/// The links a session should have, before sessions have Guidebook IDs.
+#[derive(Clone, Debug, Default)]
+pub struct SessionLinks {
+  /// The IDs of the presenters and other custom list items the session links
+  /// to.
+  pub custom_list_item_ids: Vec<u32>,
+  /// The import IDs of the sessions in the same pairing or round-table group.
+  pub peer_import_ids:      Vec<String>,
+}
+
+/// The links of each session, keyed by session import ID.
+pub type LinksByImportId = HashMap<String, SessionLinks>;
+
+/// Converts every session, returning them alongside the links each session
+/// should have.
+pub fn nasup_sessions_to_guidebook_sessions(
+  config: &Config,
+  nasup_sessions: Vec<NasupSession>,
+  schedule_tracks: &[GuidebookScheduleTrack],
+  locations: &[GuidebookLocation],
+  existing_presenters: &[GuidebookPresenter],
+) -> miette::Result<(Vec<GuidebookSession>, LinksByImportId)> {
+  let mut peer_import_ids =
+    nasup_session_peer_import_ids(&config.settings, &nasup_sessions);
+
+  let session_types = nasup_sessions
+    .iter()
+    .map(|s| s.session_type.to_string())
+    .collect::<HashSet<_>>();
+  for session_type in config.settings.registration.keys() {
+    if !session_types.contains(session_type) {
+      warn!(
+        session_type,
+        "found registration policy for a session type no session has"
+      );
+    }
+  }
+
+  let mut import_id_to_links_map = HashMap::new();
+  let sessions = nasup_sessions
+    .into_iter()
+    .map(|ns| -> miette::Result<_> {
+      let WithLinks(s, presenter_ids) = nasup_session_to_guidebook_session(
+        config,
+        ns,
+        schedule_tracks,
+        locations,
+        existing_presenters,
+      )
+      .context("failed to convert nasup session to guidebook session")?;
+      let import_id = s.import_id.clone().unwrap();
+      let links = SessionLinks {
+        custom_list_item_ids: presenter_ids,
+        peer_import_ids:      peer_import_ids
+          .remove(&import_id)
+          .unwrap_or_default(),
+      };
+      import_id_to_links_map.insert(import_id, links);
+      Ok(s)
+    })
+    .try_collect::<Vec<_>>()?;
+
+  Ok((sessions, import_id_to_links_map))
+}
+
+/// Groups paired concurrent sessions and round tables that share a room and a
+/// time slot, returning the import IDs of each session's peers.
+///
+/// Sessions of the same kind in the same room are grouped while their times
+/// overlap or abut, as long as no discriminator repeats (e.g. a second "A"
+/// starts a new pairing).
+fn nasup_session_peer_import_ids(
+  settings: &Settings,
+  nasup_sessions: &[NasupSession],
+) -> HashMap<String, Vec<String>> {
+  let mut candidates: BTreeMap<_, Vec<(&NasupSession, u8)>> = BTreeMap::new();
+  for session in nasup_sessions {
+    let (kind, discriminator) = match &session.session_type {
+      ParsedNasupSessionType::PairedConcurrent(d) => ("paired", match d {
+        PairedConcurrentDiscriminant::A => 0,
+        PairedConcurrentDiscriminant::B => 1,
+      }),
+      ParsedNasupSessionType::RoundTable(n) => ("round table", *n),
+      _ => continue,
+    };
+    let rooms = session
+      .rooms
+      .iter()
+      .map(|r| settings.canonical_room_name(&r.name))
+      .collect::<BTreeSet<_>>();
+    candidates
+      .entry((kind, rooms))
+      .or_default()
+      .push((session, discriminator));
+  }
+
+  let mut groups: Vec<Vec<&NasupSession>> = Vec::new();
+  for mut sessions in candidates.into_values() {
+    sessions.sort_by_key(|(s, _)| s.start_datetime);
+
+    let mut group = Vec::new();
+    let mut discriminators = HashSet::new();
+    let mut group_end = None;
+    for (session, discriminator) in sessions {
+      let joins_group = group_end
+        .is_some_and(|end| session.start_datetime <= end)
+        && !discriminators.contains(&discriminator);
+      if !joins_group {
+        groups.push(std::mem::take(&mut group));
+        discriminators.clear();
+        group_end = None;
+      }
+      group.push(session);
+      discriminators.insert(discriminator);
+      let session_end = session.end_datetime.unwrap_or(session.start_datetime);
+      group_end = Some(group_end.map_or(session_end, |e| session_end.max(e)));
+    }
+    groups.push(group);
+  }
+
+  let mut peer_import_ids = HashMap::new();
+  for group in groups.into_iter().filter(|g| g.len() > 1) {
+    let import_ids = group.iter().map(|s| s.primary_key()).collect::<Vec<_>>();
+    debug!(?import_ids, "grouped peer sessions");
+    for import_id in &import_ids {
+      peer_import_ids.insert(
+        import_id.clone(),
+        import_ids
+          .iter()
+          .filter(|i| *i != import_id)
+          .cloned()
+          .collect(),
+      );
+    }
+  }
+
+  peer_import_ids
+}
+
 #[instrument(skip(
   config,
   nasup_session,
@@ -104,93 +396,127 @@ pub fn nasup_session_to_guidebook_session(
   locations: &[GuidebookLocation],
   existing_presenters: &[GuidebookPresenter],
 ) -> miette::Result<WithLinks<GuidebookSession>> {
-  let intended_audience_line = nasup_session
-    .intended_audience
-    .clone()
-    .map(|ia| format!("<br><p><b>Intended for</b>: {ia}</p>"))
-    .unwrap_or_default();
-
-  let description_html = format!(
-    "<p>{description_text}</p>{intended_audience_line}",
-    description_text = html_escape::encode_text(&nasup_session.description),
+  let description_html = render_limited_description_html(
+    &config.settings.templates.session_description,
+    session_template_context(config, &nasup_session),
+    "description_html",
+    &nasup_session.title,
   );
+  let description_html = match description_html.trim() {
+    "" => Field::Clear,
+    _ => Field::Set(description_html),
+  };
 
   let session_primary_key = nasup_session.primary_key();
 
-  let schedule_tracks_to_find = nasup_session.strand.into_iter().chain(
-    Some(nasup_session.session_type)
-      .filter(ParsedNasupSessionType::included)
-      .map(|t| t.to_string()),
-  );
+  // registration fields are only managed for session types with a policy
+  let registration = config
+    .settings
+    .registration
+    .get(&nasup_session.session_type.to_string());
+  let max_capacity = match registration {
+    None => Field::Unmanaged,
+    Some(policy) if !policy.limit_capacity => Field::Clear,
+    Some(_) => {
+      let capacity = nasup_session_capacity(config, &nasup_session);
+      if capacity.is_none() {
+        warn!(
+          primary_key = session_primary_key,
+          "found no capacity for session with limited capacity"
+        );
+      }
+      capacity.into()
+    }
+  };
+
+  let schedule_tracks_to_find = nasup_session
+    .strands
+    .iter()
+    .map(|n| ScheduleTrackSource::Strand.import_id(n))
+    .chain(
+      Some(nasup_session.session_type)
+        .filter(ParsedNasupSessionType::included)
+        .map(|t| ScheduleTrackSource::SessionType.import_id(&t.to_string())),
+    )
+    .chain(
+      nasup_session
+        .intended_audience
+        .iter()
+        .filter(|_| config.settings.audience_tracks)
+        .map(|ia| ScheduleTrackSource::Audience.import_id(ia)),
+    );
   let schedule_track_ids = schedule_tracks_to_find
-    .filter_map(|stn| {
+    .filter_map(|stiid| {
       match schedule_tracks
         .iter()
-        .find(|st| st.name.as_ref().unwrap() == &stn)
+        .find(|st| st.import_id.as_ref() == Some(&stiid))
       {
         Some(st) => Some(st.clone()),
         None => {
-          warn!(name = stn, "failed to find schedule track with name");
+          warn!(
+            import_id = stiid,
+            "failed to find schedule track with import ID"
+          );
           None
         }
       }
     })
     .map(|st| st.id.expect("expected schedule track to have its id field"))
     .collect();
-  let location_to_find = nasup_session.room.name.clone();
-  let locations = match locations
-    .iter()
-    .find(|l| l.name.as_ref().unwrap() == &location_to_find)
-  {
-    Some(l) => Some(l.id.expect("expected location to have its id field")),
-    None => {
-      warn!(name = location_to_find, "failed to find location with name");
-      None
+  let locations = location_ids(config, &nasup_session.rooms, locations);
+
+  let mut presenters_to_link_to = Vec::new();
+  for approved_presenter in &nasup_session.approved_presenters {
+    let intended_presenter = nasup_presenter_to_guidebook_presenter(
+      config,
+      approved_presenter.clone(),
+    )
+    .context("failed to convert presenter to link to session")?;
+    match find_existing_presenter(&intended_presenter, existing_presenters) {
+      Some(p) => presenters_to_link_to.push(p.id.unwrap()),
+      None => warn!(
+        name = ?intended_presenter.name,
+        subtitle = ?intended_presenter.subtitle,
+        "could not find existing presenter to link to for session"
+      ),
     }
   }
-  .into_iter()
-  .collect::<HashSet<_>>();
-
-  let presenters_to_link_to =
-    nasup_session.approved_presenters.iter().flat_map(|ap| {
-      let intended_presenter =
-        nasup_presenter_to_guidebook_presenter(config, ap.clone()).unwrap();
-      let located_presenter = existing_presenters.iter().find(|ep| {
-        ep.name.as_ref() == intended_presenter.name.as_ref()
-          && ((ep.subtitle.as_ref() == intended_presenter.subtitle.as_ref())
-            || intended_presenter
-              .subtitle
-              .as_ref()
-              .is_some_and(String::is_empty))
-      });
-      let Some(id) = located_presenter.map(|p| p.id.unwrap()) else {
-        warn!(name = ?intended_presenter.name, subtitle = ?intended_presenter.subtitle, "could not find existing presenter to link to for session");
-        return None;
-      };
-      Some(id)
-    }).collect();
 
   let session = GuidebookSession {
     id: None,
     guide_id: config.guide_id as u32,
-    name: Some(nasup_session.title.clone()),
-    description_html: Some(description_html),
-    start_time: nasup_session.start_datetime,
-    end_time: Some(nasup_session.end_datetime),
-    all_day: Some(false),
-    allow_rating: Some(false),
-    add_to_schedule: Some(true),
+    name: Field::Set(nasup_session.title.clone()),
+    description_html,
+    start_time: Field::Set(nasup_session.start_datetime),
+    end_time: nasup_session.end_datetime.into(),
+    all_day: Field::Set(false),
+    allow_rating: Field::Set(false),
+    add_to_schedule: Field::Set(true),
     import_id: Some(session_primary_key.clone()),
-    locations: Some(locations),
-    schedule_tracks: Some(schedule_track_ids),
-    rank: Some(nasup_session.rank),
-    registration_start_date: None,
-    registration_end_date: None,
-    require_login: Some(false),
-    waitlist: Some(false),
-    max_capacity: None,
+    locations: Field::Set(locations),
+    schedule_tracks: Field::Set(schedule_track_ids),
+    rank: Field::Set(nasup_session.rank),
+    registration_start_date: registration
+      .map_or(Field::Unmanaged, |p| p.registration_start_date.into()),
+    registration_end_date: registration
+      .map_or(Field::Unmanaged, |p| p.registration_end_date.into()),
+    require_login: Field::Set(registration.is_some_and(|p| p.require_login)),
+    // a waitlist without a capacity is rejected
+    waitlist: Field::Set(
+      registration.is_some_and(|p| p.waitlist)
+        && max_capacity.value().is_some(),
+    ),
+    max_capacity,
   };
 
+  let session = config
+    .overrides
+    .apply_to_session(session)
+    .context("failed to apply overrides to session")?;
+  session.check_registration_prerequisites().context(format!(
+    "session {session_primary_key:?} has inconsistent registration fields"
+  ))?;
+
   debug!(
     primary_key = session_primary_key,
     "calculated guidebook session from nasup session"
@@ -199,42 +525,770 @@ pub fn nasup_session_to_guidebook_session(
   Ok(WithLinks(session, presenters_to_link_to))
 }
 
+/// The Guidebook IDs of the locations for the given rooms.
+fn location_ids(
+  config: &Config,
+  rooms: &[ParsedNasupLocation],
+  locations: &[GuidebookLocation],
+) -> HashSet<u32> {
+  rooms
+    .iter()
+    .map(|r| location_import_id(&config.settings.canonical_room_name(&r.name)))
+    .filter_map(|liid| {
+      match locations
+        .iter()
+        .find(|l| l.import_id.as_ref() == Some(&liid))
+      {
+        Some(l) => Some(l.id.expect("expected location to have its id field")),
+        None => {
+          warn!(import_id = liid, "failed to find location with import ID");
+          None
+        }
+      }
+    })
+    .collect()
+}
+
+/// Converts agenda items into sessions in the agenda track. Agenda items have
+/// no presenters or registration, so those are left to the builder.
+pub fn nasup_agenda_items_to_guidebook_sessions(
+  config: &Config,
+  agenda_items: &[NasupAgendaItem],
+  schedule_tracks: &[GuidebookScheduleTrack],
+  locations: &[GuidebookLocation],
+) -> miette::Result<Vec<GuidebookSession>> {
+  let Some(agenda_settings) = &config.settings.agenda else {
+    return Ok(Vec::new());
+  };
+  let track_import_id =
+    ScheduleTrackSource::Agenda.import_id(&agenda_settings.track_name);
+  let track_ids = schedule_tracks
+    .iter()
+    .filter(|st| st.import_id.as_ref() == Some(&track_import_id))
+    .filter_map(|st| st.id)
+    .collect::<HashSet<_>>();
+  if track_ids.is_empty() && !agenda_items.is_empty() {
+    warn!(
+      import_id = track_import_id,
+      "failed to find schedule track with import ID"
+    );
+  }
+
+  agenda_items
+    .iter()
+    .map(|item| {
+      let import_id = item.import_id();
+      let description_html = Some(render_description_html(&item.description))
+        .filter(|html| !html.is_empty())
+        .map(|html| {
+          limit_description_html(html, DESCRIPTION_HTML_LIMIT, &item.title)
+        });
+      let session = GuidebookSession {
+        id: None,
+        guide_id: config.guide_id as u32,
+        name: Field::Set(item.title.clone()),
+        description_html: description_html.into(),
+        start_time: Field::Set(item.start_datetime),
+        end_time: item.end_datetime.into(),
+        all_day: Field::Set(item.all_day),
+        allow_rating: Field::Set(false),
+        add_to_schedule: Field::Set(true),
+        import_id: Some(import_id.clone()),
+        locations: Field::Set(location_ids(config, &item.rooms, locations)),
+        schedule_tracks: Field::Set(track_ids.clone()),
+        rank: Field::Unmanaged,
+        registration_start_date: Field::Unmanaged,
+        registration_end_date: Field::Unmanaged,
+        require_login: Field::Unmanaged,
+        waitlist: Field::Unmanaged,
+        max_capacity: Field::Unmanaged,
+      };
+      debug!(import_id, "calculated guidebook session from agenda item");
+
+      config
+        .overrides
+        .apply_to_session(session)
+        .context("failed to apply overrides to agenda item")
+    })
+    .try_collect()
+}
+
+/// The values session templates can use.
+fn session_template_context(
+  config: &Config,
+  nasup_session: &NasupSession,
+) -> serde_json::Value {
+  // sessions are scheduled in conference time, like the spreadsheet
+  let start = nasup_session.start_datetime.with_timezone(&Eastern);
+  let end = nasup_session
+    .end_datetime
+    .map(|e| e.with_timezone(&Eastern));
+  let rooms = nasup_session
+    .rooms
+    .iter()
+    .map(|r| {
+      room_display_name(
+        &config.settings,
+        &config.settings.canonical_room_name(&r.name),
+      )
+    })
+    .collect::<Vec<_>>();
+  let presenter_names = nasup_session
+    .approved_presenters
+    .iter()
+    .map(|p| p.name.as_str())
+    .collect::<Vec<_>>();
+
+  serde_json::json!({
+    "title": nasup_session.title,
+    "session_type": nasup_session.session_type.to_string(),
+    "discriminator": nasup_session.session_type.discriminator(),
+    "description_html": render_description_html(&nasup_session.description),
+    "strands": nasup_session.strands,
+    "strand": nasup_session.strands.join(", "),
+    "audiences": nasup_session.intended_audience,
+    "audience": nasup_session.intended_audience.join(", "),
+    "presenters": nasup_session
+      .approved_presenters
+      .iter()
+      .map(presenter_template_context)
+      .collect::<Vec<_>>(),
+    "presenter_names": presenter_names.join(", "),
+    "co_authors": nasup_session
+      .co_authors
+      .iter()
+      .map(presenter_template_context)
+      .collect::<Vec<_>>(),
+    "presenter_roster_html": nasup_session_presenter_roster_html(
+      config,
+      nasup_session,
+    ),
+    "rooms": rooms,
+    "room": rooms.join(", "),
+    "date": start.format("%A, %B %-d").to_string(),
+    "start_time": start.format("%-I:%M %p").to_string(),
+    "end_time": end.map(|e| e.format("%-I:%M %p").to_string()),
+    "capacity": nasup_session_capacity(config, nasup_session),
+  })
+}
+
+/// A list of the session's presenters with their institutions, and of its
+/// co-authors if enabled, or nothing if the roster is disabled.
+fn nasup_session_presenter_roster_html(
+  config: &Config,
+  nasup_session: &NasupSession,
+) -> String {
+  let Some(roster) = &config.settings.presenter_roster else {
+    return String::new();
+  };
+  let list = |heading: &str, presenters: &[NasupPresenter]| {
+    if presenters.is_empty() {
+      return String::new();
+    }
+    let items = presenters
+      .iter()
+      .map(|p| {
+        let name = html_escape::encode_text(&p.name).into_owned();
+        let institutions = p
+          .first_institution
+          .iter()
+          .chain(p.second_institution.iter())
+          .map(|i| html_escape::encode_text(i).into_owned())
+          .collect::<Vec<_>>();
+        match institutions.as_slice() {
+          [] => format!("<li>{name}</li>"),
+          institutions => {
+            format!("<li>{name}, {}</li>", institutions.join(", "))
+          }
+        }
+      })
+      .collect::<String>();
+    format!("<p><b>{heading}</b></p><ul>{items}</ul>")
+  };
+
+  let co_authors = if roster.include_co_authors {
+    list("Co-authors", &nasup_session.co_authors)
+  } else {
+    String::new()
+  };
+  format!(
+    "{}{co_authors}",
+    list("Presenters", &nasup_session.approved_presenters)
+  )
+}
+
+/// The seats a session has, from the capacity column or else the combined
+/// capacity of its rooms.
+fn nasup_session_capacity(
+  config: &Config,
+  nasup_session: &NasupSession,
+) -> Option<u32> {
+  nasup_session.capacity.or_else(|| {
+    nasup_session
+      .rooms
+      .iter()
+      .map(|r| config.settings.resolve_room(&r.name)?.1.capacity)
+      .sum()
+  })
+}
+
 pub fn nasup_sessions_to_guidebook_presenters(
   config: &Config,
   nasup_sessions: &[NasupSession],
 ) -> miette::Result<Vec<GuidebookPresenter>> {
-  nasup_sessions
-    .iter()
-    .flat_map(|s| s.approved_presenters.clone().into_iter())
-    .collect::<HashSet<_>>()
+  nasup_sessions_to_unique_presenters(nasup_sessions)
     .into_iter()
     .map(|p| nasup_presenter_to_guidebook_presenter(config, p))
     .try_collect::<Vec<_>>()
 }
 
-pub fn nasup_presenter_to_guidebook_presenter(
+/// One presenter per name and institutions, which is what tells presenters
+/// apart in Guidebook. Each submission can carry its own bio, email and
+/// photo, so the most complete details are kept, ties going to the first in
+/// sort order.
+fn nasup_sessions_to_unique_presenters(
+  nasup_sessions: &[NasupSession],
+) -> Vec<NasupPresenter> {
+  let mut variants: BTreeMap<_, Vec<&NasupPresenter>> = BTreeMap::new();
+  for presenter in nasup_sessions.iter().flat_map(|s| &s.approved_presenters) {
+    let key = (
+      &presenter.name,
+      &presenter.first_institution,
+      &presenter.second_institution,
+    );
+    let presenter_variants = variants.entry(key).or_default();
+    if !presenter_variants.contains(&presenter) {
+      presenter_variants.push(presenter);
+    }
+  }
+
+  variants
+    .into_values()
+    .filter_map(|presenter_variants| {
+      let details = |p: &&NasupPresenter| {
+        let filled = [&p.bio, &p.email, &p.photo]
+          .into_iter()
+          .filter(|d| d.is_some())
+          .count();
+        (
+          Reverse(filled),
+          p.bio.clone(),
+          p.email.clone(),
+          p.photo.clone(),
+        )
+      };
+      let count = presenter_variants.len();
+      let chosen = presenter_variants.into_iter().min_by_key(details)?;
+      if count > 1 {
+        warn!(
+          name = chosen.name,
+          variants = count,
+          bio = chosen.bio,
+          email = chosen.email,
+          photo = chosen.photo,
+          "found presenter with different details across submissions, so the \
+           most complete are used"
+        );
+      }
+      Some(chosen.clone())
+    })
+    .collect()
+}
+
+/// A presenter's name and subtitle, which together tell presenters apart.
+pub type PresenterKey = (String, Option<String>);
+
+/// Finds the existing presenter where the name matches and the subtitle either
+/// matches or is supposed to be empty.
+fn find_existing_presenter<'a>(
+  intended_presenter: &GuidebookPresenter,
+  existing_presenters: &'a [GuidebookPresenter],
+) -> Option<&'a GuidebookPresenter> {
+  existing_presenters.iter().find(|ep| {
+    ep.name.value() == intended_presenter.name.value()
+      && ((ep.subtitle.value() == intended_presenter.subtitle.value())
+        || intended_presenter.subtitle.value().is_none())
+  })
+}
+
+/// The canonical names of the institutions of every approved presenter,
+/// alongside those presenters.
+fn nasup_sessions_to_institution_presenters(
+  institution_settings: &InstitutionListSettings,
+  nasup_sessions: &[NasupSession],
+) -> BTreeMap<String, HashSet<NasupPresenter>> {
+  let mut institution_presenters: BTreeMap<_, HashSet<_>> = BTreeMap::new();
+  for presenter in nasup_sessions_to_unique_presenters(nasup_sessions) {
+    let institutions =
+      [&presenter.first_institution, &presenter.second_institution];
+    for institution in institutions.into_iter().flatten() {
+      institution_presenters
+        .entry(institution_settings.canonical_name(institution))
+        .or_default()
+        .insert(presenter.clone());
+    }
+  }
+  institution_presenters
+}
+
+/// The items of the institutions custom list, named by their canonical names.
+/// Everything else about an institution is left to the builder.
+pub fn nasup_sessions_to_guidebook_institutions(
   config: &Config,
-  nasup_presenter: NasupPresenter,
-) -> miette::Result<GuidebookPresenter> {
-  let subtitle = nasup_presenter
+  institution_settings: &InstitutionListSettings,
+  nasup_sessions: &[NasupSession],
+) -> Vec<GuidebookCustomListItem> {
+  nasup_sessions_to_institution_presenters(institution_settings, nasup_sessions)
+    .into_keys()
+    .map(|name| GuidebookCustomListItem {
+      id:               None,
+      guide_id:         config.guide_id as u32,
+      import_id:        Some(custom_list_item_import_id(
+        INSTITUTION_LIST_KEY,
+        &name,
+      )),
+      name:             Field::Set(name),
+      description_html: Field::Unmanaged,
+      subtitle:         Field::Unmanaged,
+      allow_rating:     Field::Unmanaged,
+      locations:        Field::Unmanaged,
+      contact_email:    Field::Unmanaged,
+    })
+    .collect()
+}
+
+/// The presenters each existing institution should link to, keyed by the
+/// institution's Guidebook ID.
+pub fn nasup_sessions_to_institution_links(
+  config: &Config,
+  institution_settings: &InstitutionListSettings,
+  nasup_sessions: &[NasupSession],
+  existing_institutions: &[GuidebookCustomListItem],
+  existing_presenters: &[GuidebookPresenter],
+) -> miette::Result<HashMap<u32, Vec<u32>>> {
+  let institution_ids = existing_institutions
+    .iter()
+    .filter_map(|i| Some((i.import_id.clone()?, i.id?)))
+    .collect::<HashMap<_, _>>();
+
+  let mut links = HashMap::new();
+  let institution_presenters = nasup_sessions_to_institution_presenters(
+    institution_settings,
+    nasup_sessions,
+  );
+  for (name, presenters) in institution_presenters {
+    let import_id = custom_list_item_import_id(INSTITUTION_LIST_KEY, &name);
+    let Some(institution_id) = institution_ids.get(&import_id) else {
+      warn!(
+        import_id,
+        "could not find institution to link presenters to"
+      );
+      continue;
+    };
+
+    let mut presenter_ids = Vec::new();
+    for presenter in presenters {
+      let intended_presenter =
+        nasup_presenter_to_guidebook_presenter(config, presenter)?;
+      match find_existing_presenter(&intended_presenter, existing_presenters) {
+        Some(p) => presenter_ids.push(p.id.unwrap()),
+        None => warn!(
+          name = ?intended_presenter.name,
+          institution = name,
+          "could not find existing presenter to link to institution"
+        ),
+      }
+    }
+    links.insert(*institution_id, presenter_ids);
+  }
+
+  Ok(links)
+}
+
+/// The import ID of a custom list item, namespaced by the list it's in.
+pub fn custom_list_item_import_id(list: &str, name: &str) -> String {
+  format!("{list}:{name}")
+}
+
+pub fn nasup_custom_list_items_to_guidebook(
+  config: &Config,
+  list: &str,
+  list_settings: &CustomListSettings,
+  items: &[ParsedNasupCustomListItem],
+) -> Vec<GuidebookCustomListItem> {
+  let columns = &list_settings.columns;
+  // fields without a column are left to the builder
+  let field = |column: &Option<String>, value: Option<String>| match column {
+    None => Field::Unmanaged,
+    Some(_) => value.into(),
+  };
+
+  let mut names = HashSet::new();
+  items
+    .iter()
+    .filter(|item| {
+      let first = names.insert(&item.name);
+      if !first {
+        warn!(list, name = item.name, "skipping custom list item repeated");
+      }
+      first
+    })
+    .map(|item| GuidebookCustomListItem {
+      id:               None,
+      guide_id:         config.guide_id as u32,
+      name:             Field::Set(item.name.clone()),
+      description_html: field(
+        &columns.description_html,
+        item.description.as_ref().map(|d| {
+          let html = render_description_html(&ParsedNasupRichText::plain(d));
+          limit_description_html(html, DESCRIPTION_HTML_LIMIT, &item.name)
+        }),
+      ),
+      subtitle:         field(&columns.subtitle, item.subtitle.clone()),
+      allow_rating:     Field::Unmanaged,
+      import_id:        Some(custom_list_item_import_id(list, &item.name)),
+      locations:        Field::Unmanaged,
+      contact_email:    field(
+        &columns.contact_email,
+        item.contact_email.clone(),
+      ),
+    })
+    .collect()
+}
+
+/// The headshot source of every presenter that has one, keyed by the name
+/// and subtitle the presenter has in Guidebook.
+pub fn nasup_sessions_to_presenter_headshots(
+  config: &Config,
+  nasup_sessions: &[NasupSession],
+) -> miette::Result<HashMap<PresenterKey, String>> {
+  let mut headshots = HashMap::new();
+  for presenter in nasup_sessions_to_unique_presenters(nasup_sessions) {
+    let Some(photo) = presenter.photo.clone() else {
+      continue;
+    };
+    let intended_presenter =
+      nasup_presenter_to_guidebook_presenter(config, presenter)?;
+    let (Some(name), subtitle) = (
+      intended_presenter.name.value(),
+      intended_presenter.subtitle.value(),
+    ) else {
+      continue;
+    };
+    headshots.insert((name.clone(), subtitle.cloned()), photo);
+  }
+  Ok(headshots)
+}
+
+/// The values presenter templates can use, and session templates can use for
+/// each presenter.
+fn presenter_template_context(
+  nasup_presenter: &NasupPresenter,
+) -> serde_json::Value {
+  let institutions = nasup_presenter
     .first_institution
     .iter()
     .chain(nasup_presenter.second_institution.iter())
-    .cloned()
-    .intersperse(", ".to_owned())
-    .collect::<String>();
+    .collect::<Vec<_>>();
+  serde_json::json!({
+    "name": nasup_presenter.name,
+    "first_institution": nasup_presenter.first_institution,
+    "second_institution": nasup_presenter.second_institution,
+    "institutions": institutions,
+    "institution": institutions
+      .iter()
+      .map(|i| i.as_str())
+      .collect::<Vec<_>>()
+      .join(", "),
+    "bio_html": nasup_presenter
+      .bio
+      .as_ref()
+      .map(|b| render_description_html(&ParsedNasupRichText::plain(b))),
+    "email": nasup_presenter.email,
+  })
+}
+
+pub fn nasup_presenter_to_guidebook_presenter(
+  config: &Config,
+  nasup_presenter: NasupPresenter,
+) -> miette::Result<GuidebookPresenter> {
+  let templates = &config.settings.templates;
+  let context = presenter_template_context(&nasup_presenter);
+  let subtitle = templates.presenter_subtitle.render_text(&context);
+  let subtitle = Some(subtitle.trim().to_owned()).filter(|s| !s.is_empty());
+  // without a template, the bio is the description
+  let description_html = match &templates.presenter_description {
+    None => context["bio_html"]
+      .as_str()
+      .map_or(Field::Unmanaged, |html| {
+        Field::Set(limit_description_html(
+          html.to_owned(),
+          DESCRIPTION_HTML_LIMIT,
+          &nasup_presenter.name,
+        ))
+      }),
+    Some(template) => match render_limited_description_html(
+      template,
+      context,
+      "bio_html",
+      &nasup_presenter.name,
+    ) {
+      html if html.trim().is_empty() => Field::Clear,
+      html => Field::Set(html),
+    },
+  };
+  let institutions = [
+    &nasup_presenter.first_institution,
+    &nasup_presenter.second_institution,
+  ]
+  .into_iter()
+  .flatten()
+  .cloned()
+  .collect::<Vec<_>>();
 
   let presenter = GuidebookPresenter {
-    id:               None,
-    guide_id:         config.guide_id as u32,
-    name:             Some(nasup_presenter.name),
-    description_html: Some("".to_owned()),
-    subtitle:         Some(subtitle),
-    allow_rating:     None,
-    import_id:        None,
-    locations:        None,
-    contact_email:    None,
+    id: None,
+    guide_id: config.guide_id as u32,
+    name: Field::Set(nasup_presenter.name),
+    description_html,
+    subtitle: subtitle.into(),
+    allow_rating: Field::Unmanaged,
+    import_id: None,
+    locations: Field::Unmanaged,
+    contact_email: nasup_presenter.email.map_or(Field::Unmanaged, Field::Set),
   };
 
-  Ok(presenter)
+  config
+    .overrides
+    .apply_to_presenter(presenter, &institutions)
+    .context("failed to apply overrides to presenter")
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::{TimeZone, Utc};
+
+  use super::*;
+
+  fn session(
+    title: &str,
+    session_type: ParsedNasupSessionType,
+    room: &str,
+    start: (u32, u32),
+    end: (u32, u32),
+  ) -> NasupSession {
+    let at = |(h, m)| Utc.with_ymd_and_hms(2026, 3, 5, h, m, 0).unwrap();
+    let room = ParsedNasupLocation::from_string(room);
+    NasupSession {
+      start_datetime: at(start),
+      end_datetime: Some(at(end)),
+      room: room.clone(),
+      rooms: vec![room],
+      session_type,
+      title: title.to_owned(),
+      description: Default::default(),
+      capacity: None,
+      approved_presenters: Vec::new(),
+      co_authors: Vec::new(),
+      strands: Vec::new(),
+      strand_metadata: Vec::new(),
+      intended_audience: Vec::new(),
+      rank: 0.0,
+    }
+  }
+
+  fn paired(d: PairedConcurrentDiscriminant) -> ParsedNasupSessionType {
+    ParsedNasupSessionType::PairedConcurrent(d)
+  }
+
+  #[test]
+  fn groups_paired_concurrent_sessions_in_the_same_slot() {
+    let a = session(
+      "A: Tutoring",
+      paired(PairedConcurrentDiscriminant::A),
+      "Room 101",
+      (14, 0),
+      (14, 25),
+    );
+    let b = session(
+      "B: Mentoring",
+      paired(PairedConcurrentDiscriminant::B),
+      "Room 101",
+      (14, 25),
+      (14, 50),
+    );
+
+    let peers = nasup_session_peer_import_ids(&Settings::default(), &[
+      a.clone(),
+      b.clone(),
+    ]);
+
+    assert_eq!(peers[&a.primary_key()], vec![b.primary_key()]);
+    assert_eq!(peers[&b.primary_key()], vec![a.primary_key()]);
+  }
+
+  #[test]
+  fn starts_a_new_group_when_a_discriminator_repeats() {
+    let sessions = [
+      ("A: One", PairedConcurrentDiscriminant::A, (14, 0), (14, 25)),
+      (
+        "B: One",
+        PairedConcurrentDiscriminant::B,
+        (14, 25),
+        (14, 50),
+      ),
+      (
+        "A: Two",
+        PairedConcurrentDiscriminant::A,
+        (14, 50),
+        (15, 15),
+      ),
+      (
+        "B: Two",
+        PairedConcurrentDiscriminant::B,
+        (15, 15),
+        (15, 40),
+      ),
+    ]
+    .map(|(title, d, start, end)| {
+      session(title, paired(d), "Room 101", start, end)
+    });
+
+    let peers = nasup_session_peer_import_ids(&Settings::default(), &sessions);
+
+    assert_eq!(peers[&sessions[0].primary_key()], vec![
+      sessions[1].primary_key()
+    ]);
+    assert_eq!(peers[&sessions[2].primary_key()], vec![
+      sessions[3].primary_key()
+    ]);
+  }
+
+  #[test]
+  fn doesnt_group_sessions_in_other_rooms_or_slots() {
+    let sessions = [
+      session(
+        "A: Tutoring",
+        paired(PairedConcurrentDiscriminant::A),
+        "Room 101",
+        (14, 0),
+        (14, 25),
+      ),
+      session(
+        "B: Mentoring",
+        paired(PairedConcurrentDiscriminant::B),
+        "Room 102",
+        (14, 25),
+        (14, 50),
+      ),
+      session(
+        "B: Advising",
+        paired(PairedConcurrentDiscriminant::B),
+        "Room 101",
+        (16, 0),
+        (16, 25),
+      ),
+      session(
+        "Keynote",
+        ParsedNasupSessionType::General,
+        "Room 101",
+        (14, 0),
+        (15, 0),
+      ),
+    ];
+
+    let peers = nasup_session_peer_import_ids(&Settings::default(), &sessions);
+
+    assert!(peers.is_empty());
+  }
+
+  #[test]
+  fn groups_round_tables_in_the_same_room() {
+    let sessions = (1..=3)
+      .map(|n| {
+        session(
+          &format!("RT {n}: Topic {n}"),
+          ParsedNasupSessionType::RoundTable(n),
+          "Ballroom",
+          (10, 0),
+          (11, 0),
+        )
+      })
+      .collect::<Vec<_>>();
+
+    let peers = nasup_session_peer_import_ids(&Settings::default(), &sessions);
+
+    let mut first_peers = peers[&sessions[0].primary_key()].clone();
+    first_peers.sort();
+    let mut expected =
+      vec![sessions[1].primary_key(), sessions[2].primary_key()];
+    expected.sort();
+    assert_eq!(first_peers, expected);
+  }
+
+  fn presenter(
+    name: &str,
+    institution: &str,
+    bio: Option<&str>,
+    photo: Option<&str>,
+  ) -> NasupPresenter {
+    NasupPresenter {
+      name:               name.to_owned(),
+      first_institution:  Some(institution.to_owned()),
+      second_institution: None,
+      bio:                bio.map(str::to_owned),
+      email:              None,
+      photo:              photo.map(str::to_owned),
+    }
+  }
+
+  fn sessions_with(presenters: &[NasupPresenter]) -> Vec<NasupSession> {
+    presenters
+      .iter()
+      .enumerate()
+      .map(|(i, p)| NasupSession {
+        approved_presenters: vec![p.clone()],
+        ..session(
+          &format!("Session {i}"),
+          paired(PairedConcurrentDiscriminant::A),
+          "Room 101",
+          (9, i as u32),
+          (10, 0),
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn keeps_one_presenter_per_name_and_institutions() {
+    let sessions = sessions_with(&[
+      presenter("Ada", "MIT", Some("short bio"), None),
+      presenter("Ada", "MIT", Some("long bio"), Some("ada.jpg")),
+      presenter("Ada", "MIT", None, None),
+      presenter("Ada", "Yale", None, None),
+    ]);
+
+    let presenters = nasup_sessions_to_unique_presenters(&sessions);
+
+    assert_eq!(presenters, [
+      presenter("Ada", "MIT", Some("long bio"), Some("ada.jpg")),
+      presenter("Ada", "Yale", None, None),
+    ]);
+  }
+
+  #[test]
+  fn picks_presenter_details_regardless_of_submission_order() {
+    let first = presenter("Ada", "MIT", Some("bio b"), None);
+    let second = presenter("Ada", "MIT", Some("bio a"), None);
+
+    let forward = nasup_sessions_to_unique_presenters(&sessions_with(&[
+      first.clone(),
+      second.clone(),
+    ]));
+    let backward =
+      nasup_sessions_to_unique_presenters(&sessions_with(&[second, first]));
+
+    assert_eq!(forward, backward);
+    assert_eq!(forward[0].bio.as_deref(), Some("bio a"));
+  }
 }
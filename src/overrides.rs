@@ -0,0 +1,189 @@
+use std::{collections::HashMap, path::Path};
+
+use miette::{Context, IntoDiagnostic, bail};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::{debug, warn};
+
+use crate::{
+  guidebook::model::{
+    GuidebookLocation, GuidebookPresenter, GuidebookScheduleTrack,
+    GuidebookSession,
+  },
+  synth_nasup::migrate_legacy_primary_key,
+};
+
+type PinnedFields = serde_json::Map<String, serde_json::Value>;
+
+/// Guidebook field values that are pinned by hand and survive every sync.
+///
+/// Sessions are keyed by import ID, presenters by name and institutions, and
+/// schedule tracks and locations by the name the converters produce for them.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Overrides {
+  #[serde(default)]
+  pub sessions:        HashMap<String, PinnedFields>,
+  #[serde(default)]
+  pub presenters:      HashMap<String, PinnedFields>,
+  #[serde(default)]
+  pub schedule_tracks: HashMap<String, PinnedFields>,
+  #[serde(default)]
+  pub locations:       HashMap<String, PinnedFields>,
+}
+
+impl Overrides {
+  pub fn from_file(path: &Path) -> miette::Result<Self> {
+    let contents = std::fs::read_to_string(path)
+      .into_diagnostic()
+      .context(format!("failed to read overrides file at {path:?}"))?;
+    let jd = &mut serde_json::Deserializer::from_str(&contents);
+    let mut overrides: Overrides = serde_path_to_error::deserialize(jd)
+      .into_diagnostic()
+      .context("failed to parse overrides file")?;
+
+    overrides.sessions = std::mem::take(&mut overrides.sessions)
+      .into_iter()
+      .map(|(key, pinned)| match migrate_legacy_primary_key(&key) {
+        Some(migrated) => {
+          warn!(key, migrated, "found override keyed by legacy import ID");
+          (migrated, pinned)
+        }
+        None => (key, pinned),
+      })
+      .collect();
+
+    for (key, pinned) in overrides
+      .sessions
+      .iter()
+      .chain(overrides.presenters.iter())
+      .chain(overrides.schedule_tracks.iter())
+      .chain(overrides.locations.iter())
+    {
+      for field in ["id", "guide", "import_id"] {
+        if pinned.contains_key(field) {
+          bail!("override for {key:?} pins `{field}`, which can't be pinned");
+        }
+      }
+    }
+
+    Ok(overrides)
+  }
+
+  pub fn apply_to_session(
+    &self,
+    session: GuidebookSession,
+  ) -> miette::Result<GuidebookSession> {
+    let key = session.import_id.clone().unwrap_or_default();
+    apply_pinned_fields(session, &key, self.sessions.get(&key))
+  }
+
+  /// Presenters are told apart by name and institutions, so their key is the
+  /// name followed by any institutions in parentheses, like "Jane Doe
+  /// (University of Georgia, Emory University)".
+  pub fn apply_to_presenter(
+    &self,
+    presenter: GuidebookPresenter,
+    institutions: &[String],
+  ) -> miette::Result<GuidebookPresenter> {
+    let name = presenter.name.value().cloned().unwrap_or_default();
+    let key = match institutions {
+      [] => name,
+      _ => format!("{name} ({})", institutions.join(", ")),
+    };
+    apply_pinned_fields(presenter, &key, self.presenters.get(&key))
+  }
+
+  pub fn apply_to_schedule_track(
+    &self,
+    schedule_track: GuidebookScheduleTrack,
+  ) -> miette::Result<GuidebookScheduleTrack> {
+    let key = schedule_track.name.value().cloned().unwrap_or_default();
+    apply_pinned_fields(schedule_track, &key, self.schedule_tracks.get(&key))
+  }
+
+  pub fn apply_to_location(
+    &self,
+    location: GuidebookLocation,
+  ) -> miette::Result<GuidebookLocation> {
+    let key = location.name.value().cloned().unwrap_or_default();
+    apply_pinned_fields(location, &key, self.locations.get(&key))
+  }
+}
+
+fn apply_pinned_fields<T: Serialize + DeserializeOwned>(
+  entity: T,
+  key: &str,
+  pinned: Option<&PinnedFields>,
+) -> miette::Result<T> {
+  let Some(pinned) = pinned else {
+    return Ok(entity);
+  };
+
+  let mut fields = match serde_json::to_value(&entity) {
+    Ok(serde_json::Value::Object(fields)) => fields,
+    _ => bail!("failed to serialize entity {key:?} to apply overrides"),
+  };
+  fields.extend(pinned.clone());
+
+  let entity: T = serde_json::from_value(serde_json::Value::Object(fields))
+    .into_diagnostic()
+    .context(format!("failed to apply overrides to entity {key:?}"))?;
+
+  // fields the model doesn't know about are dropped silently by serde
+  let applied = serde_json::to_value(&entity).unwrap_or_default();
+  for (field, value) in pinned {
+    if !value.is_null() && applied.get(field) != Some(value) {
+      warn!(key, field, "pinned field was not applied to entity");
+    }
+  }
+  debug!(key, fields = ?pinned.keys(), "applied overrides to entity");
+
+  Ok(entity)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::guidebook::model::Field;
+
+  fn presenter(name: &str) -> GuidebookPresenter {
+    GuidebookPresenter {
+      id:               None,
+      guide_id:         1,
+      name:             Field::Set(name.to_owned()),
+      description_html: Field::Unmanaged,
+      subtitle:         Field::Unmanaged,
+      allow_rating:     Field::Unmanaged,
+      import_id:        None,
+      locations:        Field::Unmanaged,
+      contact_email:    Field::Unmanaged,
+    }
+  }
+
+  #[test]
+  fn keys_presenter_overrides_by_name_and_institutions() {
+    let overrides: Overrides = serde_json::from_value(serde_json::json!({
+      "presenters": {
+        "Jane Doe (UGA)": { "subtitle": "University of Georgia" },
+        "Jane Doe": { "subtitle": "Independent" },
+      },
+    }))
+    .unwrap();
+    let subtitle = |institutions: &[String]| {
+      overrides
+        .apply_to_presenter(presenter("Jane Doe"), institutions)
+        .unwrap()
+        .subtitle
+    };
+
+    assert_eq!(
+      subtitle(&["UGA".to_owned()]),
+      Field::Set("University of Georgia".to_owned())
+    );
+    assert_eq!(subtitle(&[]), Field::Set("Independent".to_owned()));
+    assert_eq!(
+      subtitle(&["Emory".to_owned(), "UGA".to_owned()]),
+      Field::Unmanaged
+    );
+  }
+}
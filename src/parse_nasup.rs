@@ -1,6 +1,9 @@
+pub mod parse_agenda_items;
+pub mod parse_custom_list_items;
 pub mod parse_model;
 pub mod parse_presenter_institutions;
 pub mod parse_sessions;
+pub mod parse_strand_metadata;
 pub mod parse_strands;
 
 fn find_commas_without_following_whitespace(text: &str) -> Vec<usize> {
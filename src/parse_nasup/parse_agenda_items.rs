@@ -0,0 +1,128 @@
+use calamine::Data;
+use miette::{bail, miette};
+use tracing::{debug, trace, warn};
+
+use super::{
+  parse_model::{ParsedNasupAgendaItem, ParsedNasupLocation},
+  parse_sessions::parse_rich_text_cell,
+};
+use crate::{fetch_sheet::DecodedWorksheet, settings::Settings};
+
+pub fn parse_nasup_agenda_items_from_worksheet(
+  worksheet: DecodedWorksheet,
+  settings: &Settings,
+) -> miette::Result<Vec<ParsedNasupAgendaItem>> {
+  let mut items = Vec::new();
+
+  // the columns can be anywhere, so find them by their headers
+  let header = worksheet.main.rows().next().unwrap_or_default();
+  let find_column = |name: &str| {
+    header.iter().position(
+      |d| matches!(d, Data::String(h) if h.trim().eq_ignore_ascii_case(name)),
+    )
+  };
+  let required_column = |name: &str| {
+    find_column(name).ok_or(miette!("found no column with header {name:?}"))
+  };
+  let date_column = required_column("Date")?;
+  let title_column = required_column("Title")?;
+  let start_time_column = find_column("Start Time");
+  let end_time_column = find_column("End Time");
+  let room_column = find_column("Room");
+  let description_column = find_column("Description");
+
+  // skip the header
+  let iter = worksheet.main.rows().enumerate().skip(1);
+
+  for (row_index, row) in iter {
+    let cell = |column: Option<usize>| match column.and_then(|c| row.get(c)) {
+      None | Some(Data::Empty) => None,
+      Some(Data::String(s)) if s.trim().is_empty() => None,
+      Some(d) => Some(d),
+    };
+    let string_cell = |column: Option<usize>, name: &str| {
+      Ok(match cell(column) {
+        None => None,
+        Some(Data::String(s)) => Some(s.trim().to_owned()),
+        Some(d) => bail!("{name} column is not a string, got {d:?}"),
+      })
+    };
+    let time_cell = |column: Option<usize>, name: &str| {
+      Ok(match cell(column) {
+        None => None,
+        Some(Data::DateTime(dt)) => {
+          let (_y, _m, _d, h, m, s, _millis) = dt.to_ymd_hms_milli();
+          Some(
+            chrono::NaiveTime::from_hms_opt(h as _, m as _, s as _)
+              .ok_or(miette!("{name} column is an invalid time"))?,
+          )
+        }
+        Some(d) => bail!("{name} column is not a date-time, got {d:?}"),
+      })
+    };
+
+    // skip rows without a title
+    let Some(title) = string_cell(Some(title_column), "title")? else {
+      continue;
+    };
+
+    let date = match cell(Some(date_column)) {
+      Some(Data::DateTime(dt)) => {
+        let (y, m, d, ..) = dt.to_ymd_hms_milli();
+        chrono::NaiveDate::from_ymd_opt(y as _, m as _, d as _).ok_or(
+          miette!("date column is an invalid date: y = {y}, m = {m}, d = {d}"),
+        )?
+      }
+      d => bail!("date column of {title:?} is not a date-time, got {d:?}"),
+    };
+    let start_time = time_cell(start_time_column, "start_time")?;
+    let mut end_time = time_cell(end_time_column, "end_time")?;
+    if start_time.is_none() && end_time.is_some() {
+      warn!(
+        title,
+        "ignoring end time of agenda item without a start time"
+      );
+      end_time = None;
+    }
+
+    // rooms in the registry are never split, in case their names contain a
+    // separator
+    let rooms = match string_cell(room_column, "room")? {
+      None => Vec::new(),
+      Some(room) => {
+        let room = ParsedNasupLocation::from_string(&room);
+        match settings.resolve_room(&room.name) {
+          Some(_) => vec![room],
+          None => room.split(&settings.room_separators),
+        }
+      }
+    };
+
+    let description = string_cell(description_column, "description")?;
+    let description = match description_column {
+      Some(column) => parse_rich_text_cell(
+        &worksheet.styles,
+        column,
+        row_index,
+        description.as_deref().unwrap_or_default(),
+      ),
+      None => Default::default(),
+    };
+
+    let item = ParsedNasupAgendaItem {
+      row_index,
+      date,
+      start_time,
+      end_time,
+      title,
+      rooms,
+      description,
+    };
+    trace!(?item, "parsed agenda item");
+    items.push(item);
+  }
+
+  debug!(count = items.len(), "parsed agenda items");
+
+  Ok(items)
+}
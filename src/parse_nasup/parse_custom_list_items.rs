@@ -0,0 +1,71 @@
+use calamine::Data;
+use miette::{bail, miette};
+use tracing::{debug, trace};
+
+use super::parse_model::ParsedNasupCustomListItem;
+use crate::{fetch_sheet::DecodedWorksheet, settings::CustomListSettings};
+
+pub fn parse_nasup_custom_list_items_from_worksheet(
+  worksheet: DecodedWorksheet,
+  settings: &CustomListSettings,
+) -> miette::Result<Vec<ParsedNasupCustomListItem>> {
+  let mut items = Vec::new();
+
+  // the configured columns can be anywhere, so find them by their headers
+  let header = worksheet.main.rows().next().unwrap_or_default();
+  let find_column = |name: &str| {
+    header
+      .iter()
+      .position(
+        |d| matches!(d, Data::String(h) if h.trim().eq_ignore_ascii_case(name)),
+      )
+      .ok_or(miette!("found no column with header {name:?}"))
+  };
+  let optional_column =
+    |name: &Option<String>| name.as_deref().map(find_column).transpose();
+  let name_column = find_column(&settings.columns.name)?;
+  let subtitle_column = optional_column(&settings.columns.subtitle)?;
+  let description_column = optional_column(&settings.columns.description_html)?;
+  let contact_email_column = optional_column(&settings.columns.contact_email)?;
+  let sessions_column = optional_column(&settings.sessions_column)?;
+
+  // skip the header
+  let iter = worksheet.main.rows().skip(1);
+
+  for row in iter {
+    let cell = |column: Option<usize>| -> miette::Result<Option<String>> {
+      Ok(match column.and_then(|c| row.get(c)) {
+        None | Some(Data::Empty) => None,
+        Some(Data::String(s)) if s.trim().is_empty() => None,
+        Some(Data::String(s)) => Some(s.trim().to_owned()),
+        Some(Data::Float(f)) => Some(f.to_string()),
+        Some(Data::Int(i)) => Some(i.to_string()),
+        Some(d) => bail!("custom list column is not a string, got {d:?}"),
+      })
+    };
+
+    // skip rows without a name
+    let Some(name) = cell(Some(name_column))? else {
+      continue;
+    };
+    let item = ParsedNasupCustomListItem {
+      name,
+      subtitle: cell(subtitle_column)?,
+      description: cell(description_column)?,
+      contact_email: cell(contact_email_column)?,
+      sessions: cell(sessions_column)?
+        .iter()
+        .flat_map(|s| s.split([';', '\n']))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(ToOwned::to_owned)
+        .collect(),
+    };
+    trace!(?item, "parsed custom list item");
+    items.push(item);
+  }
+
+  debug!(count = items.len(), "parsed custom list items");
+
+  Ok(items)
+}
@@ -66,6 +66,19 @@ pub enum PairedConcurrentDiscriminant {
 }
 
 impl ParsedNasupSessionType {
+  /// What tells apart sessions of this type sharing a time slot, like "A" or
+  /// a round table number.
+  pub fn discriminator(&self) -> Option<String> {
+    match self {
+      ParsedNasupSessionType::PairedConcurrent(d) => Some(match d {
+        PairedConcurrentDiscriminant::A => "A".to_owned(),
+        PairedConcurrentDiscriminant::B => "B".to_owned(),
+      }),
+      ParsedNasupSessionType::RoundTable(n) => Some(n.to_string()),
+      _ => None,
+    }
+  }
+
   pub fn included(&self) -> bool {
     !matches!(
       self,
@@ -139,6 +152,10 @@ pub struct ParsedNasupPresenterWithInstitutionBySession {
   pub session_name:       String,
   pub first_institution:  Option<String>,
   pub second_institution: Option<String>,
+  pub bio:                Option<String>,
+  pub email:              Option<String>,
+  /// A headshot URL, or a file name in the headshots directory.
+  pub photo:              Option<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -146,20 +163,105 @@ pub struct ParsedNasupSession {
   pub row_index:    usize,
   pub date:         chrono::NaiveDate,
   pub start_time:   chrono::NaiveTime,
-  pub end_time:     chrono::NaiveTime,
+  pub end_time:     Option<chrono::NaiveTime>,
+  /// The room cell as written, which session import IDs are derived from.
   pub room:         ParsedNasupLocation,
+  /// The separate rooms the room cell names.
+  pub rooms:        Vec<ParsedNasupLocation>,
   pub session_type: ParsedNasupSessionType,
   pub title:        String,
-  pub description:  String,
+  pub description:  ParsedNasupRichText,
+  /// Seats from the optional capacity column.
+  pub capacity:     Option<u32>,
   pub presenters:   Vec<ParsedNasupPresenterWithPaymentStatus>,
 }
 
+/// A row of the agenda worksheet: a break, meal, keynote or anything else on
+/// the schedule that isn't a submitted session.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ParsedNasupAgendaItem {
+  pub row_index:   usize,
+  pub date:        chrono::NaiveDate,
+  /// Empty for items that run all day.
+  pub start_time:  Option<chrono::NaiveTime>,
+  pub end_time:    Option<chrono::NaiveTime>,
+  pub title:       String,
+  pub rooms:       Vec<ParsedNasupLocation>,
+  pub description: ParsedNasupRichText,
+}
+
+/// Cell text with the formatting of its runs, as organizers typed it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ParsedNasupRichText {
+  pub runs: Vec<ParsedNasupTextRun>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ParsedNasupTextRun {
+  pub text:   String,
+  pub bold:   bool,
+  pub italic: bool,
+}
+
+impl ParsedNasupRichText {
+  pub fn plain(text: &str) -> Self {
+    ParsedNasupRichText {
+      runs: vec![ParsedNasupTextRun {
+        text:   text.to_owned(),
+        bold:   false,
+        italic: false,
+      }],
+    }
+    .trim()
+  }
+
+  /// Trims whitespace from the start and end of the text, dropping runs left
+  /// empty.
+  pub fn trim(mut self) -> Self {
+    for run in self.runs.iter_mut() {
+      run.text = run.text.trim_start().to_owned();
+      if !run.text.is_empty() {
+        break;
+      }
+    }
+    for run in self.runs.iter_mut().rev() {
+      run.text = run.text.trim_end().to_owned();
+      if !run.text.is_empty() {
+        break;
+      }
+    }
+    self.runs.retain(|r| !r.text.is_empty());
+    self
+  }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ParsedNasupStrandAndIntendedAudience {
   pub title:             String,
   pub presenters:        Vec<String>,
-  pub strand:            String,
-  pub intended_audience: String,
+  pub strands:           Vec<String>,
+  pub intended_audience: Vec<String>,
+}
+
+/// Display metadata for a strand, from the optional strand metadata sheet.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ParsedNasupStrandMetadata {
+  pub strand:      String,
+  pub description: Option<String>,
+  /// A hex color like `#2E7D32`, overriding the generated one.
+  pub color:       Option<String>,
+  pub order:       Option<f32>,
+}
+
+/// A row of a worksheet synced into a custom list, by configured column.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ParsedNasupCustomListItem {
+  pub name:          String,
+  pub subtitle:      Option<String>,
+  pub description:   Option<String>,
+  pub contact_email: Option<String>,
+  /// Titles of the sessions the item links to.
+  pub sessions:      Vec<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -177,4 +279,49 @@ impl ParsedNasupLocation {
         .join(" "),
     }
   }
+
+  /// Splits a location naming several rooms on any of the given separators.
+  /// Short parts after the first borrow its prefix, so "Ballroom A/B" becomes
+  /// "Ballroom A" and "Ballroom B", and "Room 101/102" becomes "Room 101" and
+  /// "Room 102".
+  pub fn split(&self, separators: &[String]) -> Vec<ParsedNasupLocation> {
+    let mut parts = vec![self.name.as_str()];
+    for separator in separators.iter().filter(|s| !s.is_empty()) {
+      parts = parts
+        .into_iter()
+        .flat_map(|p| p.split(separator.as_str()))
+        .collect();
+    }
+    let parts = parts
+      .into_iter()
+      .map(str::trim)
+      .filter(|p| !p.is_empty())
+      .collect::<Vec<_>>();
+
+    let Some((first, rest)) = parts.split_first() else {
+      return vec![self.clone()];
+    };
+    let prefix = first
+      .rsplit_once(' ')
+      .filter(|(_, last)| rest.iter().all(|p| is_room_suffix_of(p, last)))
+      .map(|(prefix, _)| prefix);
+
+    Some(first.to_string())
+      .into_iter()
+      .chain(rest.iter().map(|p| match prefix {
+        Some(prefix) => format!("{prefix} {p}"),
+        None => p.to_string(),
+      }))
+      .map(|name| ParsedNasupLocation::from_string(&name))
+      .collect()
+  }
+}
+
+/// Whether `part` looks like a replacement for `last`, the last word of a
+/// room name, such as "B" for "A" or "102" for "101".
+fn is_room_suffix_of(part: &str, last: &str) -> bool {
+  let numeric = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+  !part.contains(' ')
+    && ((numeric(part) && numeric(last))
+      || (part.chars().count() <= 2 && last.chars().count() <= 2))
 }
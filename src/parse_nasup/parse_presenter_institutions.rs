@@ -10,27 +10,75 @@ use crate::{
   parse_nasup::find_commas_without_following_whitespace,
 };
 
+/// Optional per-author details, in columns headed like "Author 2 Bio".
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum PresenterDetail {
+  Bio,
+  Email,
+  Photo,
+}
+
+/// The columns of optional details, keyed by zero-indexed author and detail.
+type PresenterDetailColumns = HashMap<(usize, PresenterDetail), usize>;
+
 pub fn parse_nasup_presenter_institutions_from_worksheet(
   worksheet: DecodedWorksheet,
 ) -> miette::Result<Vec<ParsedNasupPresenterWithInstitutionBySession>> {
   let mut presenter_institutions = Vec::new();
 
+  // the optional detail columns can be anywhere, so find them by their headers
+  let detail_columns = worksheet
+    .main
+    .rows()
+    .next()
+    .into_iter()
+    .flatten()
+    .enumerate()
+    .filter_map(|(x, d)| match d {
+      Data::String(h) => Some((parse_presenter_detail_header(h)?, x)),
+      _ => None,
+    })
+    .collect::<PresenterDetailColumns>();
+  debug!(?detail_columns, "looked for presenter detail columns");
+
   let iter = worksheet.main.rows().enumerate();
   // skip the header
   let iter = iter.skip(1);
 
   for (row_index, row) in iter {
-    presenter_institutions.extend(
-      parse_nasup_presenter_institutions_from_row(row_index, row)?.into_iter(),
-    );
+    presenter_institutions.extend(parse_nasup_presenter_institutions_from_row(
+      row_index,
+      row,
+      &detail_columns,
+    )?);
   }
 
   Ok(presenter_institutions)
 }
 
+/// Parses headers like "Author 2 Bio", "Author 2 Email" and "Author 2 Photo".
+fn parse_presenter_detail_header(
+  header: &str,
+) -> Option<(usize, PresenterDetail)> {
+  let header = header.to_lowercase();
+  let words = header.split_whitespace().collect::<Vec<_>>();
+  let ["author", author, detail @ ..] = words.as_slice() else {
+    return None;
+  };
+  let author = author.parse::<usize>().ok()?.checked_sub(1)?;
+  let detail = match detail {
+    ["bio"] => PresenterDetail::Bio,
+    ["email"] => PresenterDetail::Email,
+    ["photo"] | ["photo", "url"] => PresenterDetail::Photo,
+    _ => return None,
+  };
+  Some((author, detail))
+}
+
 fn parse_nasup_presenter_institutions_from_row(
   _row_index: usize,
   row: &[Data],
+  detail_columns: &PresenterDetailColumns,
 ) -> miette::Result<Vec<ParsedNasupPresenterWithInstitutionBySession>> {
   miette::ensure!(
     !row.is_empty(),
@@ -71,19 +119,39 @@ fn parse_nasup_presenter_institutions_from_row(
   // column C is blank
 
   // following columns are author 1 org 1, author 1 org 2, author 2 org 1,
-  // author 2 org 2, author 3 org 1, etc.
+  // author 2 org 2, author 3 org 1, etc., apart from any detail columns
   let mut institutions = HashMap::new();
-  row[3..].iter().enumerate().try_for_each(|(i, d)| match d {
-    Data::String(s) if s.is_empty() => Ok(()),
-    Data::String(inst) => {
-      institutions.insert((i.div_floor(2), i.rem_euclid(2)), inst.to_owned());
-      Ok(())
+  let institution_cells = row
+    .iter()
+    .enumerate()
+    .skip(3)
+    .filter(|(x, _)| !detail_columns.values().any(|c| c == x))
+    .map(|(_, d)| d);
+  for (i, d) in institution_cells.enumerate() {
+    match d {
+      Data::String(s) if s.is_empty() => {}
+      Data::String(inst) => {
+        institutions.insert((i.div_floor(2), i.rem_euclid(2)), inst.to_owned());
+      }
+      Data::Empty => {}
+      d => bail!("institution column is not a string or empty, got {d:?}"),
     }
-    Data::Empty => Ok(()),
-    d => bail!("institution column is not a string or empty, got {d:?}"),
-  })?;
+  }
   trace!(?institutions, "parsed institution columns");
 
+  let mut details = HashMap::new();
+  for (key, x) in detail_columns {
+    match row.get(*x) {
+      None | Some(Data::Empty) => {}
+      Some(Data::String(s)) if s.trim().is_empty() => {}
+      Some(Data::String(s)) => {
+        details.insert(*key, s.trim().to_owned());
+      }
+      Some(d) => bail!("presenter detail column is not a string, got {d:?}"),
+    }
+  }
+  trace!(?details, "parsed presenter detail columns");
+
   let results = split_author_names
     .into_iter()
     .enumerate()
@@ -93,7 +161,17 @@ fn parse_nasup_presenter_institutions_from_row(
         session_name:       session_name.clone(),
         first_institution:  institutions.remove(&(i, 0)),
         second_institution: institutions.remove(&(i, 1)),
+        bio:                details.remove(&(i, PresenterDetail::Bio)),
+        email:              details.remove(&(i, PresenterDetail::Email)),
+        photo:              details.remove(&(i, PresenterDetail::Photo)),
       };
+      if record.email.as_ref().is_some_and(|e| !e.contains('@')) {
+        warn!(
+          name,
+          email = record.email,
+          "found presenter email without @"
+        );
+      }
       debug!("collected author-institution-session record: {record:#?}");
       record
     })
@@ -105,6 +183,12 @@ fn parse_nasup_presenter_institutions_from_row(
       "some institutions were not matched with author names"
     );
   }
+  if !details.is_empty() {
+    warn!(
+      ?details,
+      "some presenter details were not matched with author names"
+    );
+  }
 
   Ok(results)
 }
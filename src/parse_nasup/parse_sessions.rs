@@ -9,15 +9,25 @@ use crate::{
   fetch_sheet::DecodedWorksheet,
   parse_nasup::parse_model::{
     ParsedNasupLocation, ParsedNasupPresenterWithPaymentStatus,
-    ParsedNasupSessionType,
+    ParsedNasupRichText, ParsedNasupSessionType, ParsedNasupTextRun,
   },
+  settings::Settings,
 };
 
 pub fn parse_nasup_sessions_from_worksheet(
   worksheet: DecodedWorksheet,
+  settings: &Settings,
 ) -> miette::Result<Vec<ParsedNasupSession>> {
   let mut sessions = Vec::new();
 
+  // the optional capacity column can be anywhere, so find it by its header
+  let capacity_column = worksheet.main.rows().next().and_then(|header| {
+    header.iter().position(|d| {
+      matches!(d, Data::String(h) if h.trim().eq_ignore_ascii_case("capacity"))
+    })
+  });
+  debug!(?capacity_column, "looked for capacity column");
+
   let iter = worksheet.main.rows().enumerate();
   // skip the header
   let iter = iter.skip(1);
@@ -27,16 +37,48 @@ pub fn parse_nasup_sessions_from_worksheet(
       row_index,
       row,
       &worksheet.styles,
+      settings,
+      capacity_column,
     )?);
   }
   Ok(sessions)
 }
 
-#[instrument(skip(row, styles))]
+/// Reads the formatted runs of a cell, falling back to its plain text.
+pub(super) fn parse_rich_text_cell(
+  styles: &umya_spreadsheet::Worksheet,
+  column: usize,
+  row_index: usize,
+  plain: &str,
+) -> ParsedNasupRichText {
+  // calamine only reads plain text, so bold and italic runs come from umya
+  match styles
+    .get_cell((column as u32 + 1, row_index as u32 + 1))
+    .and_then(|c| c.get_raw_value().get_rich_text())
+  {
+    Some(rich_text) => ParsedNasupRichText {
+      runs: rich_text
+        .get_rich_text_elements()
+        .iter()
+        .map(|e| ParsedNasupTextRun {
+          text:   e.get_text().to_owned(),
+          bold:   e.get_run_properties().is_some_and(|f| *f.get_bold()),
+          italic: e.get_run_properties().is_some_and(|f| *f.get_italic()),
+        })
+        .collect(),
+    }
+    .trim(),
+    None => ParsedNasupRichText::plain(plain),
+  }
+}
+
+#[instrument(skip(row, styles, settings, capacity_column))]
 pub fn parse_nasup_session_from_row(
   row_index: usize,
   row: &[Data],
   styles: &umya_spreadsheet::Worksheet,
+  settings: &Settings,
+  capacity_column: Option<usize>,
 ) -> miette::Result<ParsedNasupSession> {
   miette::ensure!(
     !row.is_empty(),
@@ -84,13 +126,18 @@ pub fn parse_nasup_session_from_row(
 
   // end time
   let end_time = match row.index(3) {
-    Data::DateTime(dt) => dt,
-    d => bail!("end_time column is not a date-time, got {d:?}"),
+    Data::DateTime(dt) => Some(dt),
+    Data::Empty => None,
+    d => bail!("end_time column is not a date-time or empty, got {d:?}"),
   };
-  let (_y, _m, _d, h, m, s, _millis) = end_time.to_ymd_hms_milli();
-  let end_time = chrono::NaiveTime::from_hms_opt(h as _, m as _, s as _)
-    .ok_or(miette!("end_time column is an invalid time"))?;
-  trace!(%end_time, "parsed end_time column");
+  let end_time = end_time
+    .map(|end_time| {
+      let (_y, _m, _d, h, m, s, _millis) = end_time.to_ymd_hms_milli();
+      chrono::NaiveTime::from_hms_opt(h as _, m as _, s as _)
+        .ok_or(miette!("end_time column is an invalid time"))
+    })
+    .transpose()?;
+  trace!(?end_time, "parsed end_time column");
 
   // room
   let room = match row.index(4) {
@@ -98,6 +145,14 @@ pub fn parse_nasup_session_from_row(
     d => bail!("room column is not a string, got {d:?}"),
   };
   trace!(room, "parsed room column");
+  let room = ParsedNasupLocation::from_string(&room);
+  // rooms in the registry are never split, in case their names contain a
+  // separator
+  let rooms = match settings.resolve_room(&room.name) {
+    Some(_) => vec![room.clone()],
+    None => room.split(&settings.room_separators),
+  };
+  trace!(?rooms, "split room column");
 
   // title
   // parsed before type because we need the title for discriminators
@@ -121,17 +176,35 @@ pub fn parse_nasup_session_from_row(
 
   // description
   let description = match row.index(7) {
-    Data::String(d) => d.trim().to_owned(),
-    Data::Empty => String::new(),
+    Data::String(d) => d.as_str(),
+    Data::Empty => "",
     d => bail!("description column is not a string, got {d:?}"),
   };
-  trace!(description, "parsed description column");
+  let description = parse_rich_text_cell(styles, 7, row_index, description);
+  trace!(?description, "parsed description column");
+
+  // capacity
+  let capacity = match capacity_column.and_then(|c| row.get(c)) {
+    None | Some(Data::Empty) => None,
+    Some(Data::Float(c)) if *c >= 0.0 => Some(*c as u32),
+    Some(Data::Int(c)) if *c >= 0 => Some(*c as u32),
+    Some(Data::String(c)) if c.trim().is_empty() => None,
+    Some(Data::String(c)) => Some(
+      c.trim()
+        .parse::<u32>()
+        .into_diagnostic()
+        .context(format!("capacity column is not a number, got {c:?}"))?,
+    ),
+    Some(d) => bail!("capacity column is not a number, got {d:?}"),
+  };
+  trace!(?capacity, "parsed capacity column");
 
   // presenters
   let presenter_cells = row[8..]
     .iter()
     .enumerate()
     .map(|(i, d)| (i + 8, d))
+    .filter(|(i, _)| Some(*i) != capacity_column)
     .filter(|(_, d)| !matches!(d, Data::Empty))
     .collect::<Vec<_>>();
 
@@ -175,10 +248,12 @@ pub fn parse_nasup_session_from_row(
     date,
     start_time,
     end_time,
-    room: ParsedNasupLocation::from_string(&room),
+    room,
+    rooms,
     session_type,
     title,
     description,
+    capacity,
     presenters,
   };
 
@@ -0,0 +1,139 @@
+use std::ops::Index;
+
+use calamine::Data;
+use miette::bail;
+use tracing::{debug, trace, warn};
+
+use super::parse_model::ParsedNasupStrandMetadata;
+use crate::{fetch_sheet::DecodedWorksheet, track_colors::normalize_hex_color};
+
+pub fn parse_nasup_strand_metadata_from_worksheet(
+  worksheet: DecodedWorksheet,
+) -> miette::Result<Vec<ParsedNasupStrandMetadata>> {
+  let mut strand_metadata = Vec::new();
+
+  // skip the header
+  let iter = worksheet.main.rows().skip(1);
+
+  for row in iter {
+    // skip rows without a strand name
+    if matches!(row.first(), None | Some(Data::Empty)) {
+      continue;
+    }
+    strand_metadata.push(parse_nasup_strand_metadata_from_row(row)?);
+  }
+
+  Ok(strand_metadata)
+}
+
+fn parse_nasup_strand_metadata_from_row(
+  row: &[Data],
+) -> miette::Result<ParsedNasupStrandMetadata> {
+  miette::ensure!(
+    !row.is_empty(),
+    "failed to parse XLSX row as NASUP strand metadata: no strand cell"
+  );
+  // the other columns are optional, and trailing empty cells may be trimmed
+  // out of the range
+
+  // strand
+  let strand = match row.index(0) {
+    Data::String(s) => s.trim().to_owned(),
+    d => bail!("strand column is not a string, got {d:?}"),
+  };
+  trace!(strand, "parsed strand column");
+
+  // description
+  let description = match row.get(1) {
+    Some(Data::String(d)) if d.trim().is_empty() => None,
+    Some(Data::String(d)) => Some(d.trim().to_owned()),
+    None | Some(Data::Empty) => None,
+    Some(d) => bail!("description column is not a string or empty, got {d:?}"),
+  };
+  trace!(?description, "parsed description column");
+
+  // color
+  let color = match row.get(2) {
+    Some(Data::String(c)) if c.trim().is_empty() => None,
+    Some(Data::String(c)) => {
+      let color = normalize_hex_color(c);
+      if color.is_none() {
+        warn!(strand, color = c, "ignoring color that is not a hex color");
+      }
+      color
+    }
+    None | Some(Data::Empty) => None,
+    Some(d) => bail!("color column is not a string or empty, got {d:?}"),
+  };
+  trace!(?color, "parsed color column");
+
+  // order
+  let order = match row.get(3) {
+    Some(Data::Float(o)) => Some(*o as f32),
+    Some(Data::Int(o)) => Some(*o as f32),
+    Some(Data::String(o)) if o.trim().is_empty() => None,
+    Some(Data::String(o)) => match o.trim().parse::<f32>() {
+      Ok(o) => Some(o),
+      Err(_) => bail!("order column is not a number, got {o:?}"),
+    },
+    None | Some(Data::Empty) => None,
+    Some(d) => bail!("order column is not a number or empty, got {d:?}"),
+  };
+  trace!(?order, "parsed order column");
+
+  let strand_metadata = ParsedNasupStrandMetadata {
+    strand,
+    description,
+    color,
+    order,
+  };
+
+  debug!("parsed full strand metadata: {strand_metadata:#?}");
+
+  Ok(strand_metadata)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn string(s: &str) -> Data { Data::String(s.to_owned()) }
+
+  #[test]
+  fn parses_rows_without_optional_columns() {
+    let metadata =
+      parse_nasup_strand_metadata_from_row(&[string(" Equity ")]).unwrap();
+
+    assert_eq!(metadata.strand, "Equity");
+    assert_eq!(metadata.description, None);
+    assert_eq!(metadata.color, None);
+    assert_eq!(metadata.order, None);
+  }
+
+  #[test]
+  fn parses_rows_without_an_order_column() {
+    let metadata = parse_nasup_strand_metadata_from_row(&[
+      string("Equity"),
+      string("About equity"),
+      string("2e7d32"),
+    ])
+    .unwrap();
+
+    assert_eq!(metadata.description.as_deref(), Some("About equity"));
+    assert_eq!(metadata.color.as_deref(), Some("#2E7D32"));
+    assert_eq!(metadata.order, None);
+  }
+
+  #[test]
+  fn parses_full_rows() {
+    let metadata = parse_nasup_strand_metadata_from_row(&[
+      string("Equity"),
+      Data::Empty,
+      Data::Empty,
+      Data::Float(2.0),
+    ])
+    .unwrap();
+
+    assert_eq!(metadata.order, Some(2.0));
+  }
+}
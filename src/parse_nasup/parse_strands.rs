@@ -73,6 +73,15 @@ fn parse_nasup_strands_from_row(
     d => bail!("strands column is not a string, got {d:?}"),
   };
   trace!(strand, "parsed strand column");
+  // strand names can contain commas, so multiple strands are separated by
+  // semicolons or line breaks
+  let split_strands = strand
+    .split([';', '\n'])
+    .map(str::trim)
+    .filter(|s| !s.is_empty())
+    .map(ToOwned::to_owned)
+    .collect::<Vec<_>>();
+  trace!(?split_strands, "found and split strands");
 
   // intended_audience
   let intended_audience = match row.index(3) {
@@ -90,6 +99,8 @@ fn parse_nasup_strands_from_row(
   }
   let split_intended_audience = intended_audience
     .split(", ")
+    .map(str::trim)
+    .filter(|ia| !ia.is_empty())
     .map(ToOwned::to_owned)
     .collect::<Vec<_>>();
   trace!(
@@ -98,10 +109,10 @@ fn parse_nasup_strands_from_row(
   );
 
   let strands_and_intended_audience = ParsedNasupStrandAndIntendedAudience {
-    title: session_name,
-    presenters: split_author_names,
-    strand,
-    intended_audience,
+    title:             session_name,
+    presenters:        split_author_names,
+    strands:           split_strands,
+    intended_audience: split_intended_audience,
   };
 
   debug!(
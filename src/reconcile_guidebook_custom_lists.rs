@@ -0,0 +1,389 @@
+use std::collections::{HashMap, HashSet};
+
+use miette::{Context, IntoDiagnostic};
+use tracing::{debug, instrument, warn};
+
+use crate::{
+  config::{Config, INSTITUTION_LIST_KEY},
+  fetch_sheet::fetch_xlsx_from_google_sheets,
+  guidebook::{
+    Modification, fetch_all_guidebook_entities,
+    model::{GuidebookCustomListItem, GuidebookPresenter, SyncBase},
+  },
+  nasup_to_guidebook::{
+    LinksByImportId, custom_list_item_import_id,
+    nasup_custom_list_items_to_guidebook,
+    nasup_sessions_to_guidebook_institutions,
+    nasup_sessions_to_institution_links,
+  },
+  parse_nasup::parse_custom_list_items::parse_nasup_custom_list_items_from_worksheet,
+  store::SyncedEntityKind,
+  synchronize_links::{LinksPlan, plan_custom_list_item_links},
+  synth_nasup::{NasupSession, strip_session_discriminators_from_name},
+};
+
+/// A configured custom list and the items its worksheet says it should have.
+#[derive(Debug)]
+pub struct IntendedCustomList {
+  pub key:            String,
+  pub list_id:        u32,
+  pub items:          Vec<GuidebookCustomListItem>,
+  /// The titles of the sessions each item links to, keyed by item import ID.
+  /// Lists without a sessions column have none, and their links are left
+  /// alone.
+  pub session_titles: Option<HashMap<String, Vec<String>>>,
+}
+
+#[derive(Debug)]
+pub struct CustomListReconciliation {
+  pub list_key:             String,
+  pub list_id:              u32,
+  pub items_to_create:      Vec<GuidebookCustomListItem>,
+  /// Patches of existing items, keyed by Guidebook ID.
+  pub items_to_update:      HashMap<u32, GuidebookCustomListItem>,
+  /// Sync bases for existing items, to be recorded once updated.
+  pub sync_bases_to_record: HashMap<u32, SyncBase>,
+  /// The fetched state of each existing item to update, journaled as its
+  /// prior state.
+  pub prior_states:         HashMap<u32, serde_json::Value>,
+}
+
+impl CustomListReconciliation {
+  pub fn is_empty(&self) -> bool {
+    self.items_to_create.is_empty() && self.items_to_update.is_empty()
+  }
+
+  #[instrument(skip(self, config), fields(list = self.list_key))]
+  pub async fn execute_reconciliation(
+    &self,
+    config: &Config,
+  ) -> miette::Result<()> {
+    for item in &self.items_to_create {
+      debug!(name = ?item.name, "creating guidebook custom list item");
+      let new_item = crate::guidebook::upsert_guidebook_entity(
+        config,
+        item.clone(),
+        "/custom-list-items/",
+        Modification::Create,
+      )
+      .await
+      .context("failed to create custom list item during reconciliation")?;
+
+      crate::guidebook::relate_guidebook_custom_list_item(
+        config,
+        self.list_id,
+        new_item.id.unwrap(),
+      )
+      .await
+      .context(
+        "failed to relate new custom list item to its list during \
+         reconciliation",
+      )?;
+      config
+        .store
+        .record_sync_base(
+          SyncedEntityKind::CustomListItem,
+          new_item.id.unwrap(),
+          &SyncBase::of(item),
+        )
+        .context("failed to record sync base of created custom list item")?;
+      debug!(
+        name = ?item.name,
+        "successfully created guidebook custom list item"
+      );
+    }
+
+    for (id, item) in &self.items_to_update {
+      debug!(id, ?item, "updating guidebook custom list item");
+      crate::guidebook::upsert_guidebook_entity(
+        config,
+        item.clone(),
+        "/custom-list-items/",
+        Modification::Update {
+          id:     *id,
+          before: self.prior_states.get(id).cloned(),
+        },
+      )
+      .await
+      .context("failed to update custom list item during reconciliation")?;
+      debug!(id, ?item, "successfully updated guidebook custom list item");
+    }
+
+    for (id, base) in &self.sync_bases_to_record {
+      config
+        .store
+        .record_sync_base(SyncedEntityKind::CustomListItem, *id, base)
+        .context("failed to record sync base of existing custom list item")?;
+    }
+
+    Ok(())
+  }
+}
+
+pub fn reconcile_intended_and_existing_guidebook_custom_list_items(
+  intended_list: &IntendedCustomList,
+  existing_items: &[GuidebookCustomListItem],
+  sync_bases: &HashMap<u32, SyncBase>,
+) -> miette::Result<CustomListReconciliation> {
+  let mut items_to_create = Vec::new();
+  let mut items_to_update = HashMap::new();
+  let mut sync_bases_to_record = HashMap::new();
+  let mut prior_states = HashMap::new();
+  let mut matched_ids = HashSet::new();
+
+  for intended_item in &intended_list.items {
+    // match by import ID first, then adopt an item without one by name, which
+    // covers items the builder added by hand before the list was synced
+    let existing_item = existing_items
+      .iter()
+      .find(|ei| {
+        ei.import_id.is_some() && ei.import_id == intended_item.import_id
+      })
+      .or_else(|| {
+        existing_items.iter().find(|ei| {
+          ei.import_id.is_none()
+            && ei.name.value().is_some()
+            && ei.name.value() == intended_item.name.value()
+        })
+      });
+
+    let Some(existing_item) = existing_item else {
+      items_to_create.push(intended_item.clone());
+      continue;
+    };
+
+    let existing_id = existing_item.id.unwrap();
+    matched_ids.insert(existing_id);
+    let merged = GuidebookCustomListItem::generate_patch_diff(
+      intended_item,
+      existing_item,
+      sync_bases.get(&existing_id),
+    );
+    merged
+      .report
+      .log(intended_item.name.value().map_or("", String::as_str));
+    sync_bases_to_record.insert(existing_id, merged.next_base);
+
+    let patch = merged.patch;
+    if !patch.is_empty_patch() {
+      debug!(
+        ?intended_item,
+        ?existing_item,
+        "generated non-empty custom list item patch diff"
+      );
+      prior_states.insert(
+        existing_id,
+        serde_json::to_value(existing_item)
+          .into_diagnostic()
+          .context("failed to serialize existing custom list item")?,
+      );
+      items_to_update.insert(existing_id, patch);
+    }
+  }
+
+  // rows removed from the worksheet leave their items behind for someone to
+  // delete, since the builder may have linked them elsewhere
+  for stale_item in existing_items.iter().filter(|ei| {
+    ei.import_id.is_some()
+      && ei.id.is_some_and(|id| !matched_ids.contains(&id))
+      && sync_bases.contains_key(&ei.id.unwrap())
+  }) {
+    warn!(
+      list = intended_list.key,
+      name = ?stale_item.name,
+      import_id = stale_item.import_id,
+      "found previously synced custom list item that is no longer intended"
+    );
+  }
+
+  Ok(CustomListReconciliation {
+    list_key: intended_list.key.clone(),
+    list_id: intended_list.list_id,
+    items_to_create,
+    items_to_update,
+    sync_bases_to_record,
+    prior_states,
+  })
+}
+
+/// Fetches and parses the worksheet of every configured custom list, along
+/// with the institutions list if it's enabled.
+#[instrument(skip(config, sessions))]
+pub async fn fetch_intended_custom_lists(
+  config: &Config,
+  sessions: &[NasupSession],
+) -> miette::Result<Vec<IntendedCustomList>> {
+  let mut lists = config.settings.custom_lists.iter().collect::<Vec<_>>();
+  lists.sort_by_key(|(key, _)| *key);
+
+  let mut intended_lists = Vec::new();
+  for (key, list_settings) in lists {
+    let worksheet =
+      fetch_xlsx_from_google_sheets(&list_settings.spreadsheet_id)
+        .await?
+        .get_worksheet(&list_settings.worksheet)
+        .context(format!("failed to get worksheet of custom list {key:?}"))?;
+    let items =
+      parse_nasup_custom_list_items_from_worksheet(worksheet, list_settings)
+        .context(format!("failed to parse items of custom list {key:?}"))?;
+
+    let session_titles = list_settings.sessions_column.as_ref().map(|_| {
+      items
+        .iter()
+        .map(|item| {
+          (
+            custom_list_item_import_id(key, &item.name),
+            item.sessions.clone(),
+          )
+        })
+        .collect()
+    });
+    intended_lists.push(IntendedCustomList {
+      key: key.clone(),
+      list_id: config.custom_list_id(key)?,
+      items: nasup_custom_list_items_to_guidebook(
+        config,
+        key,
+        list_settings,
+        &items,
+      ),
+      session_titles,
+    });
+  }
+
+  if let Some(institution_settings) = &config.settings.institutions {
+    intended_lists.push(IntendedCustomList {
+      key:            INSTITUTION_LIST_KEY.to_owned(),
+      list_id:        config.institution_list_id()?,
+      items:          nasup_sessions_to_guidebook_institutions(
+        config,
+        institution_settings,
+        sessions,
+      ),
+      session_titles: None,
+    });
+  }
+
+  Ok(intended_lists)
+}
+
+/// Plans the links between institutions and their presenters, if the
+/// institutions list is enabled.
+pub async fn plan_institution_links(
+  config: &Config,
+  sessions: &[NasupSession],
+  existing_presenters: &[GuidebookPresenter],
+  existing_items: &HashMap<u32, Vec<GuidebookCustomListItem>>,
+) -> miette::Result<Option<LinksPlan>> {
+  let Some(institution_settings) = &config.settings.institutions else {
+    return Ok(None);
+  };
+  let institution_links = nasup_sessions_to_institution_links(
+    config,
+    institution_settings,
+    sessions,
+    existing_items
+      .get(&config.institution_list_id()?)
+      .map_or(&[], Vec::as_slice),
+    existing_presenters,
+  )?;
+  let presenter_ids = existing_presenters.iter().filter_map(|p| p.id).collect();
+
+  plan_custom_list_item_links(config, institution_links, &presenter_ids)
+    .await
+    .map(Some)
+}
+
+/// Fetches the items of every configured custom list, keyed by list ID.
+pub async fn fetch_existing_custom_list_items(
+  config: &Config,
+  intended_lists: &[IntendedCustomList],
+) -> miette::Result<HashMap<u32, Vec<GuidebookCustomListItem>>> {
+  let mut existing_items = HashMap::new();
+  for list in intended_lists {
+    existing_items.insert(
+      list.list_id,
+      fetch_all_guidebook_entities(
+        config,
+        &format!("/custom-list-items/?custom_lists={}", list.list_id),
+      )
+      .await?,
+    );
+  }
+
+  Ok(existing_items)
+}
+
+/// Adds the links between sessions and the items of lists with a sessions
+/// column, returning the IDs of the items whose links are now owned.
+///
+/// Session titles are matched ignoring case, and then also ignoring prefixes
+/// like "A: " or "RT 1: ", so a title links every session it names.
+pub fn link_custom_list_items_to_sessions(
+  intended_lists: &[IntendedCustomList],
+  existing_items: &HashMap<u32, Vec<GuidebookCustomListItem>>,
+  sessions: &[NasupSession],
+  links: &mut LinksByImportId,
+) -> HashSet<u32> {
+  let mut sessions_by_title: HashMap<String, Vec<String>> = HashMap::new();
+  let mut sessions_by_stripped_title: HashMap<String, Vec<String>> =
+    HashMap::new();
+  for session in sessions {
+    let import_id = session.primary_key();
+    let stripped_title = strip_session_discriminators_from_name(&session.title);
+    sessions_by_title
+      .entry(session.title.to_lowercase())
+      .or_default()
+      .push(import_id.clone());
+    sessions_by_stripped_title
+      .entry(stripped_title.to_lowercase())
+      .or_default()
+      .push(import_id);
+  }
+
+  let mut owned_item_ids = HashSet::new();
+  for list in intended_lists {
+    let Some(session_titles) = &list.session_titles else {
+      continue;
+    };
+    for item in existing_items.get(&list.list_id).into_iter().flatten() {
+      let (Some(id), Some(titles)) = (
+        item.id,
+        item
+          .import_id
+          .as_ref()
+          .and_then(|iid| session_titles.get(iid)),
+      ) else {
+        continue;
+      };
+      owned_item_ids.insert(id);
+
+      for title in titles {
+        let import_ids =
+          sessions_by_title.get(&title.to_lowercase()).or_else(|| {
+            sessions_by_stripped_title.get(
+              &strip_session_discriminators_from_name(title).to_lowercase(),
+            )
+          });
+        let Some(import_ids) = import_ids else {
+          warn!(
+            list = list.key,
+            item = ?item.name,
+            title,
+            "found no session with the title a custom list item links to"
+          );
+          continue;
+        };
+        for import_id in import_ids {
+          links
+            .entry(import_id.clone())
+            .or_default()
+            .custom_list_item_ids
+            .push(id);
+        }
+      }
+    }
+  }
+
+  owned_item_ids
+}
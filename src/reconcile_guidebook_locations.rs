@@ -1,60 +1,250 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 
-use miette::Context;
+use miette::{Context, IntoDiagnostic};
 use tracing::{debug, instrument};
 
 use crate::{
   config::Config,
-  guidebook::{Modification, model::GuidebookLocation},
+  guidebook::{
+    Modification,
+    model::{GuidebookLocation, SyncBase},
+  },
+  nasup_to_guidebook::location_import_id,
+  settings::Settings,
+  store::SyncedEntityKind,
 };
 
 #[derive(Debug)]
 pub struct LocationsReconciliation {
-  pub strands_to_create: Vec<GuidebookLocation>,
+  pub locations_to_create:  Vec<GuidebookLocation>,
+  /// Patches of existing locations, keyed by Guidebook ID.
+  pub locations_to_update:  HashMap<u32, GuidebookLocation>,
+  /// Sync bases for existing locations, to be recorded once updated.
+  pub sync_bases_to_record: HashMap<u32, SyncBase>,
+  /// The fetched state of each existing location to update, journaled as its
+  /// prior state.
+  pub prior_states:         HashMap<u32, serde_json::Value>,
 }
 
 impl LocationsReconciliation {
+  pub fn is_empty(&self) -> bool {
+    self.locations_to_create.is_empty() && self.locations_to_update.is_empty()
+  }
+
   #[instrument(skip(self, config))]
   pub async fn execute_reconciliation(
     &self,
     config: &Config,
   ) -> miette::Result<()> {
-    for strand in &self.strands_to_create {
+    for location in &self.locations_to_create {
       debug!(
-        name = strand.name.as_ref().unwrap(),
+        name = ?location.name,
         "creating guidebook location"
       );
-      crate::guidebook::upsert_guidebook_entity(
+      let new_location = crate::guidebook::upsert_guidebook_entity(
         config,
-        strand.clone(),
+        location.clone(),
         "/locations/",
         Modification::Create,
       )
       .await
       .context("failed to create location during reconciliation")?;
+      config
+        .store
+        .record_sync_base(
+          SyncedEntityKind::Location,
+          new_location.id.unwrap(),
+          &SyncBase::of(location),
+        )
+        .context("failed to record sync base of created location")?;
       debug!(
-        name = strand.name.as_ref().unwrap(),
+        name = ?location.name,
         "successfully created guidebook location"
       );
     }
 
+    for (id, location) in &self.locations_to_update {
+      debug!(id, ?location, "updating guidebook location");
+      crate::guidebook::upsert_guidebook_entity(
+        config,
+        location.clone(),
+        "/locations/",
+        Modification::Update {
+          id:     *id,
+          before: self.prior_states.get(id).cloned(),
+        },
+      )
+      .await
+      .context("failed to update location during reconciliation")?;
+      debug!(id, ?location, "successfully updated guidebook location");
+    }
+
+    for (id, base) in &self.sync_bases_to_record {
+      config
+        .store
+        .record_sync_base(SyncedEntityKind::Location, *id, base)
+        .context("failed to record sync base of existing location")?;
+    }
+
     Ok(())
   }
 }
 
 pub fn reconcile_intended_and_existing_guidebook_locations(
+  settings: &Settings,
   intended_locations: &[GuidebookLocation],
   existing_locations: &[GuidebookLocation],
+  sync_bases: &HashMap<u32, SyncBase>,
 ) -> miette::Result<LocationsReconciliation> {
-  let existing_names = existing_locations
-    .iter()
-    .map(|l| l.name.as_ref().unwrap())
-    .collect::<HashSet<_>>();
-  let strands_to_create = intended_locations
-    .iter()
-    .filter(|l| !existing_names.contains(l.name.as_ref().unwrap()))
-    .cloned()
-    .collect();
-
-  Ok(LocationsReconciliation { strands_to_create })
+  let mut locations_to_create = Vec::new();
+  let mut locations_to_update = HashMap::new();
+  let mut sync_bases_to_record = HashMap::new();
+  let mut prior_states = HashMap::new();
+
+  for intended_location in intended_locations {
+    // match by import ID first, then adopt a location without one by name,
+    // which covers locations created before they had import IDs, and then by
+    // any other name of its room, which covers those created before the room
+    // had a building or floor
+    let unadopted = || {
+      existing_locations
+        .iter()
+        .filter(|el| el.import_id.is_none())
+        .filter_map(|el| Some((el, el.name.value()?)))
+    };
+    let existing_location = existing_locations
+      .iter()
+      .find(|el| {
+        el.import_id.is_some() && el.import_id == intended_location.import_id
+      })
+      .or_else(|| {
+        unadopted()
+          .find(|(_, name)| Some(*name) == intended_location.name.value())
+          .map(|(el, _)| el)
+      })
+      .or_else(|| {
+        unadopted()
+          .find(|(_, name)| {
+            is_other_room_name(settings, name, intended_location)
+          })
+          .map(|(el, _)| el)
+      });
+
+    let Some(existing_location) = existing_location else {
+      locations_to_create.push(intended_location.clone());
+      continue;
+    };
+
+    let existing_id = existing_location.id.unwrap();
+    let merged = GuidebookLocation::generate_patch_diff(
+      intended_location,
+      existing_location,
+      sync_bases.get(&existing_id),
+    );
+    merged
+      .report
+      .log(intended_location.name.value().map_or("", String::as_str));
+    sync_bases_to_record.insert(existing_id, merged.next_base);
+
+    let patch = merged.patch;
+    if !patch.is_empty_patch() {
+      debug!(
+        ?intended_location,
+        ?existing_location,
+        "generated non-empty location patch diff"
+      );
+      prior_states.insert(
+        existing_id,
+        serde_json::to_value(existing_location)
+          .into_diagnostic()
+          .context("failed to serialize existing location")?,
+      );
+      locations_to_update.insert(existing_id, patch);
+    }
+  }
+
+  Ok(LocationsReconciliation {
+    locations_to_create,
+    locations_to_update,
+    sync_bases_to_record,
+    prior_states,
+  })
+}
+
+/// Whether `name` is the canonical name, an alias or the configured display
+/// name of the room an intended location is for.
+fn is_other_room_name(
+  settings: &Settings,
+  name: &str,
+  intended_location: &GuidebookLocation,
+) -> bool {
+  let is_intended = |room: &str| {
+    intended_location.import_id.as_deref()
+      == Some(location_import_id(room).as_str())
+  };
+  is_intended(&settings.canonical_room_name(name))
+    || settings.rooms.iter().any(|(room, room_settings)| {
+      room_settings.name.as_deref() == Some(name) && is_intended(room)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{guidebook::model::Field, settings::RoomSettings};
+
+  fn location(
+    id: Option<u32>,
+    name: &str,
+    room: Option<&str>,
+  ) -> GuidebookLocation {
+    GuidebookLocation {
+      id,
+      guide_id: 1,
+      name: Field::Set(name.to_owned()),
+      import_id: room.map(location_import_id),
+      location_type: Field::Unmanaged,
+      latitude: Field::Unmanaged,
+      longitude: Field::Unmanaged,
+    }
+  }
+
+  fn settings() -> Settings {
+    let mut settings = Settings::default();
+    settings.rooms.insert("Room 101".to_owned(), RoomSettings {
+      aliases: vec!["Rm 101".to_owned()],
+      name: Some("Seminar Room".to_owned()),
+      building: Some("Hall".to_owned()),
+      ..Default::default()
+    });
+    settings
+  }
+
+  fn adopted_id(existing_name: &str) -> Option<u32> {
+    let intended = [location(None, "Seminar Room (Hall)", Some("Room 101"))];
+    let existing = [location(Some(7), existing_name, None)];
+
+    let reconciliation = reconcile_intended_and_existing_guidebook_locations(
+      &settings(),
+      &intended,
+      &existing,
+      &HashMap::new(),
+    )
+    .unwrap();
+
+    reconciliation.locations_to_update.keys().next().copied()
+  }
+
+  #[test]
+  fn adopts_locations_by_any_name_of_their_room() {
+    assert_eq!(adopted_id("Seminar Room (Hall)"), Some(7));
+    assert_eq!(adopted_id("Room 101"), Some(7));
+    assert_eq!(adopted_id("rm  101"), Some(7));
+    assert_eq!(adopted_id("Seminar Room"), Some(7));
+  }
+
+  #[test]
+  fn doesnt_adopt_locations_of_other_rooms() {
+    assert_eq!(adopted_id("Room 102"), None);
+  }
 }
@@ -1,15 +1,30 @@
-use miette::Context;
+use std::collections::HashMap;
+
+use miette::{Context, IntoDiagnostic};
 use tracing::{debug, instrument, warn};
 
-use crate::{config::Config, guidebook::model::GuidebookPresenter};
+use crate::{
+  config::Config,
+  guidebook::model::{GuidebookPresenter, SyncBase},
+  store::SyncedEntityKind,
+};
 
 #[derive(Debug)]
 pub struct PresenterReconciliation {
   pub presenters_to_create: Vec<GuidebookPresenter>,
   pub presenters_to_update: Vec<GuidebookPresenter>,
+  /// Sync bases for existing presenters, to be recorded once updated.
+  pub sync_bases_to_record: HashMap<u32, SyncBase>,
+  /// The fetched state of each existing presenter to update, journaled as its
+  /// prior state.
+  pub prior_states:         HashMap<u32, serde_json::Value>,
 }
 
 impl PresenterReconciliation {
+  pub fn is_empty(&self) -> bool {
+    self.presenters_to_create.is_empty() && self.presenters_to_update.is_empty()
+  }
+
   #[instrument(skip(self, config))]
   pub async fn execute_reconciliation(
     &self,
@@ -18,7 +33,7 @@ impl PresenterReconciliation {
     for presenter in &self.presenters_to_create {
       // create presenter
       debug!(
-        name = presenter.name.as_ref().unwrap(),
+        name = ?presenter.name,
         "creating guidebook presenter"
       );
       let new_presenter = crate::guidebook::upsert_guidebook_entity(
@@ -31,23 +46,26 @@ impl PresenterReconciliation {
       .context("failed to create presenter during reconciliation")?;
 
       // relate presenter to custom list
-      let relation_payload = serde_json::json!({
-        "custom_list": config.presenter_custom_list_id,
-        "custom_list_item": new_presenter.id.unwrap(),
-      });
-      crate::guidebook::upsert_guidebook_entity(
+      crate::guidebook::relate_guidebook_custom_list_item(
         config,
-        relation_payload,
-        "/custom-list-item-relations/",
-        crate::guidebook::Modification::Create,
+        config.presenter_list_id()?,
+        new_presenter.id.unwrap(),
       )
       .await
       .context(
         "failed to relate new presenter to presenter list during \
          reconciliation",
       )?;
+      config
+        .store
+        .record_sync_base(
+          SyncedEntityKind::Presenter,
+          new_presenter.id.unwrap(),
+          &SyncBase::of(presenter),
+        )
+        .context("failed to record sync base of created presenter")?;
       debug!(
-        name = presenter.name.as_ref().unwrap(),
+        name = ?presenter.name,
         "successfully created guidebook presenter"
       );
     }
@@ -59,7 +77,8 @@ impl PresenterReconciliation {
         presenter.clone(),
         "/custom-list-items/",
         crate::guidebook::Modification::Update {
-          id: presenter.id.unwrap(),
+          id:     presenter.id.unwrap(),
+          before: self.prior_states.get(&presenter.id.unwrap()).cloned(),
         },
       )
       .await
@@ -67,6 +86,13 @@ impl PresenterReconciliation {
       debug!(?presenter, "successfully updated guidebook presenter");
     }
 
+    for (id, base) in &self.sync_bases_to_record {
+      config
+        .store
+        .record_sync_base(SyncedEntityKind::Presenter, *id, base)
+        .context("failed to record sync base of existing presenter")?;
+    }
+
     Ok(())
   }
 }
@@ -74,33 +100,47 @@ impl PresenterReconciliation {
 pub fn reconcile_intended_and_existing_guidebook_presenters(
   intended_presenters: &[GuidebookPresenter],
   existing_presenters: &[GuidebookPresenter],
+  sync_bases: &HashMap<u32, SyncBase>,
 ) -> miette::Result<PresenterReconciliation> {
   let mut presenters_to_create = Vec::new();
   let mut presenters_to_update = Vec::new();
+  let mut sync_bases_to_record = HashMap::new();
+  let mut prior_states = HashMap::new();
 
   for intended_presenter in intended_presenters {
     // find the existing presenter where the name matches and the subtitle
     // either matches or is supposed to be empty (and is not present in the
     // existing)
     match existing_presenters.iter().find(|ep| {
-      ep.name.as_ref() == intended_presenter.name.as_ref()
-        && ((ep.subtitle.as_ref() == intended_presenter.subtitle.as_ref())
-          || intended_presenter
-            .subtitle
-            .as_ref()
-            .is_some_and(String::is_empty))
+      ep.name.value() == intended_presenter.name.value()
+        && ((ep.subtitle.value() == intended_presenter.subtitle.value())
+          || intended_presenter.subtitle.value().is_none())
     }) {
       Some(existing_presenter) => {
-        let patch = GuidebookPresenter::generate_patch_diff(
+        let existing_id = existing_presenter.id.unwrap();
+        let merged = GuidebookPresenter::generate_patch_diff(
           intended_presenter,
           existing_presenter,
+          sync_bases.get(&existing_id),
         );
+        merged
+          .report
+          .log(intended_presenter.name.value().map_or("", String::as_str));
+        sync_bases_to_record.insert(existing_id, merged.next_base);
+
+        let patch = merged.patch;
         if !patch.is_empty_patch() {
           warn!(
             ?intended_presenter,
             ?existing_presenter,
             "generated non-empty patch diff"
           );
+          prior_states.insert(
+            existing_id,
+            serde_json::to_value(existing_presenter)
+              .into_diagnostic()
+              .context("failed to serialize existing presenter")?,
+          );
           presenters_to_update.push(patch);
         }
       }
@@ -113,5 +153,7 @@ pub fn reconcile_intended_and_existing_guidebook_presenters(
   Ok(PresenterReconciliation {
     presenters_to_create,
     presenters_to_update,
+    sync_bases_to_record,
+    prior_states,
   })
 }
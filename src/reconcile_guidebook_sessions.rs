@@ -1,18 +1,32 @@
 use std::collections::{HashMap, HashSet};
 
-use miette::Context;
+use miette::{Context, IntoDiagnostic};
 use tracing::{debug, error, instrument, warn};
 
-use crate::{config::Config, guidebook::model::GuidebookSession};
+use crate::{
+  config::Config,
+  guidebook::model::{GuidebookSession, SyncBase},
+  store::SyncedEntityKind,
+  synth_nasup::migrate_legacy_primary_key,
+};
 
 #[derive(Debug)]
 pub struct SessionReconciliation {
   pub _orphaned_existing_import_ids: HashSet<String>,
   pub sessions_to_create:            Vec<GuidebookSession>,
   pub sessions_to_update:            Vec<GuidebookSession>,
+  /// Sync bases for existing sessions, to be recorded once updated.
+  pub sync_bases_to_record:          HashMap<u32, SyncBase>,
+  /// The fetched state of each existing session to update, journaled as its
+  /// prior state.
+  pub prior_states:                  HashMap<u32, serde_json::Value>,
 }
 
 impl SessionReconciliation {
+  pub fn is_empty(&self) -> bool {
+    self.sessions_to_create.is_empty() && self.sessions_to_update.is_empty()
+  }
+
   #[instrument(skip(self, config))]
   pub async fn execute_reconciliation(
     &self,
@@ -23,7 +37,7 @@ impl SessionReconciliation {
         import_id = session.import_id.as_ref().unwrap(),
         "creating guidebook session"
       );
-      crate::guidebook::upsert_guidebook_entity(
+      let new_session = crate::guidebook::upsert_guidebook_entity(
         config,
         session.clone(),
         "/sessions/",
@@ -31,6 +45,14 @@ impl SessionReconciliation {
       )
       .await
       .context("failed to create session during reconciliation")?;
+      config
+        .store
+        .record_sync_base(
+          SyncedEntityKind::Session,
+          new_session.id.unwrap(),
+          &SyncBase::of(session),
+        )
+        .context("failed to record sync base of created session")?;
       debug!(
         import_id = session.import_id.as_ref().unwrap(),
         "successfully created guidebook session"
@@ -47,7 +69,8 @@ impl SessionReconciliation {
         session.clone(),
         "/sessions/",
         crate::guidebook::Modification::Update {
-          id: session.id.unwrap(),
+          id:     session.id.unwrap(),
+          before: self.prior_states.get(&session.id.unwrap()).cloned(),
         },
       )
       .await
@@ -58,6 +81,13 @@ impl SessionReconciliation {
       );
     }
 
+    for (id, base) in &self.sync_bases_to_record {
+      config
+        .store
+        .record_sync_base(SyncedEntityKind::Session, *id, base)
+        .context("failed to record sync base of existing session")?;
+    }
+
     Ok(())
   }
 }
@@ -65,6 +95,7 @@ impl SessionReconciliation {
 pub fn reconcile_intended_and_existing_guidebook_sessions(
   intended_sessions: &[GuidebookSession],
   existing_sessions: &[GuidebookSession],
+  sync_bases: &HashMap<u32, SyncBase>,
 ) -> miette::Result<SessionReconciliation> {
   let mut import_id_collision_map: HashMap<String, Vec<GuidebookSession>> =
     HashMap::new();
@@ -108,8 +139,11 @@ pub fn reconcile_intended_and_existing_guidebook_sessions(
   for existing in existing_sessions {
     match &existing.import_id {
       Some(import_id) => {
-        existing_sessions_by_import_id
-          .insert(import_id.clone(), existing.clone());
+        // sessions synced before the end time left their import IDs are
+        // matched by the current form, and given it when updated
+        let import_id =
+          migrate_legacy_primary_key(import_id).unwrap_or(import_id.clone());
+        existing_sessions_by_import_id.insert(import_id, existing.clone());
       }
       None => {
         warn!(
@@ -160,22 +194,97 @@ pub fn reconcile_intended_and_existing_guidebook_sessions(
     .collect::<Vec<_>>();
 
   // sessions which both exist and are intended
-  let sessions_to_update = intended_import_ids
-    .intersection(&existing_import_ids)
-    .map(|iid| {
-      let intended = intended_sessions_by_import_id.get(iid).unwrap();
-      let existing = existing_sessions_by_import_id.get(iid).unwrap();
-      let mut patch_session =
-        GuidebookSession::generate_patch_diff(intended, existing);
-      patch_session.id = existing.id;
-      patch_session
-    })
-    .filter(|s| !s.is_empty_patch())
-    .collect::<Vec<_>>();
+  let mut sessions_to_update = Vec::new();
+  let mut sync_bases_to_record = HashMap::new();
+  let mut prior_states = HashMap::new();
+  for iid in intended_import_ids.intersection(&existing_import_ids) {
+    let intended = intended_sessions_by_import_id.get(iid).unwrap();
+    let existing = existing_sessions_by_import_id.get(iid).unwrap();
+    let existing_id = existing.id.unwrap();
+    let merged = GuidebookSession::generate_patch_diff(
+      intended,
+      existing,
+      sync_bases.get(&existing_id),
+    );
+    merged.report.log(iid);
+    sync_bases_to_record.insert(existing_id, merged.next_base);
+
+    let mut patch_session = merged.patch;
+    patch_session.id = existing.id;
+    let migrated = existing.import_id.as_ref() != Some(iid);
+    if migrated {
+      debug!(
+        import_id = iid,
+        legacy_import_id = existing.import_id,
+        "migrating legacy session import ID"
+      );
+      patch_session.import_id = Some(iid.clone());
+    }
+    if migrated || !patch_session.is_empty_patch() {
+      prior_states.insert(
+        existing_id,
+        serde_json::to_value(existing)
+          .into_diagnostic()
+          .context("failed to serialize existing session")?,
+      );
+      sessions_to_update.push(patch_session);
+    }
+  }
 
   Ok(SessionReconciliation {
     _orphaned_existing_import_ids: orphaned_existing_import_ids,
     sessions_to_create,
     sessions_to_update,
+    sync_bases_to_record,
+    prior_states,
   })
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn session(id: Option<u32>, import_id: &str) -> GuidebookSession {
+    serde_json::from_value(serde_json::json!({
+      "id": id,
+      "guide": 1,
+      "name": "A: Tutoring",
+      "import_id": import_id,
+    }))
+    .unwrap()
+  }
+
+  #[test]
+  fn adopts_sessions_with_legacy_import_ids() {
+    let key = |end: Option<&str>| {
+      let mut key = serde_json::json!({
+        "name": "A: Tutoring",
+        "room": "Room 101",
+        "start": "2026-03-05T14:00:00Z",
+      });
+      if let Some(end) = end {
+        key["end"] = end.into();
+      }
+      key.to_string()
+    };
+    let (current, legacy) = (key(None), key(Some("2026-03-05T14:25:00Z")));
+
+    let reconciliation = reconcile_intended_and_existing_guidebook_sessions(
+      &[session(None, &current)],
+      &[session(Some(7), &legacy)],
+      &HashMap::new(),
+    )
+    .unwrap();
+
+    assert!(reconciliation.sessions_to_create.is_empty());
+    assert_eq!(reconciliation.sessions_to_update.len(), 1);
+    let patch = &reconciliation.sessions_to_update[0];
+    assert_eq!(patch.id, Some(7));
+    assert_eq!(patch.import_id.as_deref(), Some(current.as_str()));
+    // the fetched session is journaled as it was, without fetching it again
+    assert_eq!(
+      reconciliation.prior_states[&7]["import_id"].as_str(),
+      Some(legacy.as_str())
+    );
+  }
+}
@@ -1,16 +1,34 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use miette::Context;
-use tracing::{debug, instrument};
+use miette::{Context, IntoDiagnostic};
+use tracing::{debug, info, instrument, warn};
 
-use crate::{config::Config, guidebook::model::GuidebookScheduleTrack};
+use crate::{
+  config::Config,
+  guidebook::{
+    Modification,
+    model::{GuidebookScheduleTrack, SyncBase},
+  },
+  store::SyncedEntityKind,
+};
 
 #[derive(Debug)]
 pub struct StrandsReconciliation {
-  pub strands_to_create: Vec<GuidebookScheduleTrack>,
+  pub strands_to_create:    Vec<GuidebookScheduleTrack>,
+  /// Patches of existing schedule tracks, keyed by Guidebook ID.
+  pub strands_to_update:    HashMap<u32, GuidebookScheduleTrack>,
+  /// Sync bases for existing schedule tracks, to be recorded once updated.
+  pub sync_bases_to_record: HashMap<u32, SyncBase>,
+  /// The fetched state of each existing schedule track to update, journaled
+  /// as its prior state.
+  pub prior_states:         HashMap<u32, serde_json::Value>,
 }
 
 impl StrandsReconciliation {
+  pub fn is_empty(&self) -> bool {
+    self.strands_to_create.is_empty() && self.strands_to_update.is_empty()
+  }
+
   #[instrument(skip(self, config))]
   pub async fn execute_reconciliation(
     &self,
@@ -18,18 +36,54 @@ impl StrandsReconciliation {
   ) -> miette::Result<()> {
     for strand in &self.strands_to_create {
       debug!(
-        name = strand.name.as_ref().unwrap(),
+        name = ?strand.name,
         "creating guidebook schedule track"
       );
-      crate::guidebook::create_guidebook_schedule_track(config, strand.clone())
-        .await
-        .context("failed to create schedule track during reconciliation")?;
+      let new_strand = crate::guidebook::upsert_guidebook_entity(
+        config,
+        strand.clone(),
+        "/schedule-tracks/",
+        Modification::Create,
+      )
+      .await
+      .context("failed to create schedule track during reconciliation")?;
+      config
+        .store
+        .record_sync_base(
+          SyncedEntityKind::ScheduleTrack,
+          new_strand.id.unwrap(),
+          &SyncBase::of(strand),
+        )
+        .context("failed to record sync base of created schedule track")?;
       debug!(
-        name = strand.name.as_ref().unwrap(),
+        name = ?strand.name,
         "successfully created guidebook schedule track"
       );
     }
 
+    for (id, strand) in &self.strands_to_update {
+      debug!(id, ?strand, "updating guidebook schedule track");
+      crate::guidebook::upsert_guidebook_entity(
+        config,
+        strand.clone(),
+        "/schedule-tracks/",
+        Modification::Update {
+          id:     *id,
+          before: self.prior_states.get(id).cloned(),
+        },
+      )
+      .await
+      .context("failed to update schedule track during reconciliation")?;
+      debug!(id, ?strand, "successfully updated guidebook schedule track");
+    }
+
+    for (id, base) in &self.sync_bases_to_record {
+      config
+        .store
+        .record_sync_base(SyncedEntityKind::ScheduleTrack, *id, base)
+        .context("failed to record sync base of existing schedule track")?;
+    }
+
     Ok(())
   }
 }
@@ -37,16 +91,229 @@ impl StrandsReconciliation {
 pub fn reconcile_intended_and_existing_guidebook_schedule_tracks(
   intended_schedule_tracks: &[GuidebookScheduleTrack],
   existing_schedule_tracks: &[GuidebookScheduleTrack],
+  sync_bases: &HashMap<u32, SyncBase>,
 ) -> miette::Result<StrandsReconciliation> {
-  let existing_names = existing_schedule_tracks
-    .iter()
-    .map(|st| st.name.as_ref().unwrap())
-    .collect::<HashSet<_>>();
-  let strands_to_create = intended_schedule_tracks
+  let mut strands_to_create = Vec::new();
+  let mut strands_to_update = HashMap::new();
+  let mut sync_bases_to_record = HashMap::new();
+  let mut prior_states = HashMap::new();
+  let mut matched_ids = HashSet::new();
+
+  let mut merge_into_existing = |intended_strand: &GuidebookScheduleTrack,
+                                 existing_strand: &GuidebookScheduleTrack|
+   -> miette::Result<()> {
+    let existing_id = existing_strand.id.unwrap();
+    let merged = GuidebookScheduleTrack::generate_patch_diff(
+      intended_strand,
+      existing_strand,
+      sync_bases.get(&existing_id),
+    );
+    merged
+      .report
+      .log(intended_strand.name.value().map_or("", String::as_str));
+    sync_bases_to_record.insert(existing_id, merged.next_base);
+
+    let patch = merged.patch;
+    if !patch.is_empty_patch() {
+      debug!(
+        ?intended_strand,
+        ?existing_strand,
+        "generated non-empty schedule track patch diff"
+      );
+      prior_states.insert(
+        existing_id,
+        serde_json::to_value(existing_strand)
+          .into_diagnostic()
+          .context("failed to serialize existing schedule track")?,
+      );
+      strands_to_update.insert(existing_id, patch);
+    }
+    Ok(())
+  };
+
+  let mut unmatched_strands = Vec::new();
+  for intended_strand in intended_schedule_tracks {
+    // match by import ID first, then adopt a track without one by name, which
+    // covers tracks created before they had import IDs
+    let existing_strand = existing_schedule_tracks
+      .iter()
+      .find(|est| {
+        est.import_id.is_some() && est.import_id == intended_strand.import_id
+      })
+      .or_else(|| {
+        existing_schedule_tracks.iter().find(|est| {
+          est.import_id.is_none()
+            && est.name.value().is_some()
+            && est.name.value() == intended_strand.name.value()
+        })
+      });
+
+    match existing_strand {
+      Some(existing_strand) => {
+        matched_ids.insert(existing_strand.id.unwrap());
+        merge_into_existing(intended_strand, existing_strand)?;
+      }
+      None => unmatched_strands.push(intended_strand),
+    }
+  }
+
+  let stale_strands = existing_schedule_tracks
     .iter()
-    .filter(|st| !existing_names.contains(st.name.as_ref().unwrap()))
-    .cloned()
-    .collect();
+    .filter(|est| {
+      est.import_id.is_some()
+        && est.id.is_some_and(|id| !matched_ids.contains(&id))
+        && sync_bases.contains_key(&est.id.unwrap())
+    })
+    .collect::<Vec<_>>();
+
+  // a track whose source was renamed gets a new import ID, so when a kind of
+  // track has exactly one new track and one stale one, the stale one is
+  // renamed, keeping its sessions and builder edits
+  let mut renamed_ids = HashSet::new();
+  for intended_strand in &unmatched_strands {
+    let namespace = import_id_namespace(intended_strand);
+    let in_namespace =
+      |st: &GuidebookScheduleTrack| import_id_namespace(st) == namespace;
+    let new_count =
+      unmatched_strands.iter().filter(|s| in_namespace(s)).count();
+    let stale_in_namespace = stale_strands
+      .iter()
+      .filter(|s| in_namespace(s))
+      .collect::<Vec<_>>();
+
+    match (new_count, stale_in_namespace.as_slice()) {
+      (1, [stale_strand]) => {
+        info!(
+          from = ?stale_strand.name,
+          to = ?intended_strand.name,
+          "renaming schedule track whose source was renamed"
+        );
+        renamed_ids.insert(stale_strand.id.unwrap());
+        merge_into_existing(intended_strand, stale_strand)?;
+      }
+      _ => strands_to_create.push((*intended_strand).clone()),
+    }
+  }
 
-  Ok(StrandsReconciliation { strands_to_create })
+  // anything else is left behind for someone to move sessions off of and
+  // delete
+  for stale_strand in stale_strands
+    .into_iter()
+    .filter(|est| !renamed_ids.contains(&est.id.unwrap()))
+  {
+    warn!(
+      name = ?stale_strand.name,
+      import_id = stale_strand.import_id,
+      "found previously synced schedule track that is no longer intended"
+    );
+  }
+
+  Ok(StrandsReconciliation {
+    strands_to_create,
+    strands_to_update,
+    sync_bases_to_record,
+    prior_states,
+  })
+}
+
+/// The kind of source a track's import ID comes from, e.g. `strand`.
+fn import_id_namespace(
+  schedule_track: &GuidebookScheduleTrack,
+) -> Option<&str> {
+  schedule_track
+    .import_id
+    .as_deref()
+    .and_then(|iid| iid.split_once(':'))
+    .map(|(namespace, _)| namespace)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::guidebook::model::Field;
+
+  fn track(id: Option<u32>, import_id: &str) -> GuidebookScheduleTrack {
+    GuidebookScheduleTrack {
+      id,
+      guide_id: 1,
+      name: Field::Set(import_id.split_once(':').unwrap().1.to_owned()),
+      description_html: Field::Unmanaged,
+      color: Field::Set("#336699".to_owned()),
+      rank: Field::Unmanaged,
+      import_id: Some(import_id.to_owned()),
+    }
+  }
+
+  fn synced(tracks: &[GuidebookScheduleTrack]) -> HashMap<u32, SyncBase> {
+    tracks
+      .iter()
+      .map(|t| (t.id.unwrap(), SyncBase::of(t)))
+      .collect()
+  }
+
+  #[test]
+  fn renames_the_only_stale_track_of_a_kind() {
+    let existing = [
+      track(Some(1), "strand:Equity"),
+      track(Some(2), "session-type:General"),
+    ];
+    let intended = [
+      track(None, "strand:Equity and Access"),
+      track(None, "session-type:General"),
+    ];
+
+    let reconciliation =
+      reconcile_intended_and_existing_guidebook_schedule_tracks(
+        &intended,
+        &existing,
+        &synced(&existing),
+      )
+      .unwrap();
+
+    assert!(reconciliation.strands_to_create.is_empty());
+    let patch = &reconciliation.strands_to_update[&1];
+    assert_eq!(patch.name, Field::Set("Equity and Access".to_owned()));
+    assert_eq!(patch.import_id.as_deref(), Some("strand:Equity and Access"));
+    assert!(!reconciliation.strands_to_update.contains_key(&2));
+  }
+
+  #[test]
+  fn creates_tracks_when_renames_are_ambiguous() {
+    let existing = [
+      track(Some(1), "strand:Equity"),
+      track(Some(2), "strand:Access"),
+    ];
+    let intended = [
+      track(None, "strand:Equity and Access"),
+      track(None, "strand:Belonging"),
+    ];
+
+    let reconciliation =
+      reconcile_intended_and_existing_guidebook_schedule_tracks(
+        &intended,
+        &existing,
+        &synced(&existing),
+      )
+      .unwrap();
+
+    assert_eq!(reconciliation.strands_to_create.len(), 2);
+    assert!(reconciliation.strands_to_update.is_empty());
+  }
+
+  #[test]
+  fn doesnt_rename_tracks_of_another_kind() {
+    let existing = [track(Some(1), "audience:Faculty")];
+    let intended = [track(None, "strand:Equity")];
+
+    let reconciliation =
+      reconcile_intended_and_existing_guidebook_schedule_tracks(
+        &intended,
+        &existing,
+        &synced(&existing),
+      )
+      .unwrap();
+
+    assert_eq!(reconciliation.strands_to_create.len(), 1);
+    assert!(reconciliation.strands_to_update.is_empty());
+  }
 }
@@ -0,0 +1,436 @@
+use serde_json::Value;
+use tracing::warn;
+
+use crate::{
+  parse_nasup::parse_model::{ParsedNasupRichText, ParsedNasupTextRun},
+  templates::Template,
+};
+
+/// How many characters Guidebook accepts in a `description_html` field.
+pub const DESCRIPTION_HTML_LIMIT: usize = 20_000;
+
+/// What ends a description cut short to fit [`DESCRIPTION_HTML_LIMIT`].
+const TRUNCATION_MARKER: &str = "<p>…</p>";
+
+/// The closing tags of the top-level blocks descriptions are made of.
+const BLOCK_ENDS: [&str; 3] = ["</p>", "</ul>", "</ol>"];
+
+#[derive(Debug)]
+enum Block {
+  Paragraph(Vec<ParsedNasupTextRun>),
+  List {
+    ordered: bool,
+    items:   Vec<Vec<ParsedNasupTextRun>>,
+  },
+}
+
+/// Renders spreadsheet cell text as Guidebook HTML. Every line becomes a
+/// paragraph, lines starting with a dash, bullet or number become list
+/// items, bare URLs and emails become links, and bold and italic runs are
+/// kept.
+pub fn render_description_html(text: &ParsedNasupRichText) -> String {
+  let mut blocks: Vec<Block> = Vec::new();
+  for line in split_lines(text) {
+    let plain = line.iter().map(|r| r.text.as_str()).collect::<String>();
+    if plain.trim().is_empty() {
+      // a blank line separates lists that would otherwise be joined
+      blocks.push(Block::Paragraph(Vec::new()));
+      continue;
+    }
+
+    match list_marker(&plain) {
+      Some((ordered, marker_len)) => {
+        let item = trim_runs(strip_prefix_bytes(line, marker_len));
+        match blocks.last_mut() {
+          Some(Block::List { ordered: o, items }) if *o == ordered => {
+            items.push(item)
+          }
+          _ => blocks.push(Block::List {
+            ordered,
+            items: vec![item],
+          }),
+        }
+      }
+      None => blocks.push(Block::Paragraph(trim_runs(line))),
+    }
+  }
+
+  blocks
+    .iter()
+    .map(|block| match block {
+      Block::Paragraph(runs) if runs.is_empty() => String::new(),
+      Block::Paragraph(runs) => format!("<p>{}</p>", render_runs(runs)),
+      Block::List { ordered, items } => {
+        let tag = if *ordered { "ol" } else { "ul" };
+        let items = items
+          .iter()
+          .map(|item| format!("<li>{}</li>", render_runs(item)))
+          .collect::<String>();
+        format!("<{tag}>{items}</{tag}>")
+      }
+    })
+    .collect()
+}
+
+/// Renders `template` with the description body under `body_key` cut down so
+/// that the whole fits [`DESCRIPTION_HTML_LIMIT`], keeping everything the
+/// template adds around the body.
+pub fn render_limited_description_html(
+  template: &Template,
+  mut context: Value,
+  body_key: &str,
+  name: &str,
+) -> String {
+  if let Some(body) = context[body_key].as_str().map(str::to_owned) {
+    // measured with the marker as the body, so that sections shown only with
+    // a body count too
+    context[body_key] = TRUNCATION_MARKER.into();
+    let around = template
+      .render_html(&context)
+      .chars()
+      .count()
+      .saturating_sub(TRUNCATION_MARKER.chars().count());
+    context[body_key] = limit_description_html(
+      body,
+      DESCRIPTION_HTML_LIMIT.saturating_sub(around),
+      name,
+    )
+    .into();
+  }
+
+  let html = template.render_html(&context);
+  let length = html.chars().count();
+  if length > DESCRIPTION_HTML_LIMIT {
+    warn!(
+      name,
+      length,
+      limit = DESCRIPTION_HTML_LIMIT,
+      "found templated description longer than guidebook allows even with its \
+       body cut short"
+    );
+  }
+  html
+}
+
+/// Cuts HTML made of top-level blocks, like that of
+/// [`render_description_html`], down to `limit` characters by dropping the
+/// blocks that don't fit.
+pub fn limit_description_html(
+  html: String,
+  limit: usize,
+  name: &str,
+) -> String {
+  let length = html.chars().count();
+  if length <= limit {
+    return html;
+  }
+
+  let budget = limit.saturating_sub(TRUNCATION_MARKER.chars().count());
+  // the end of the last block that fits, in bytes and in characters
+  let mut cut = (0, 0);
+  for (chars, (i, c)) in html.char_indices().enumerate() {
+    if c != '<' {
+      continue;
+    }
+    let Some(tag) = BLOCK_ENDS.iter().find(|tag| html[i..].starts_with(**tag))
+    else {
+      continue;
+    };
+    // the tags are ASCII, so their bytes are their characters
+    let end = (i + tag.len(), chars + tag.len());
+    if end.1 > budget {
+      break;
+    }
+    cut = end;
+  }
+  warn!(
+    name,
+    length,
+    limit,
+    kept = cut.1,
+    "found description longer than guidebook allows, so it was truncated"
+  );
+  format!("{}{TRUNCATION_MARKER}", &html[..cut.0])
+}
+
+/// Splits runs on line breaks, so each line has the runs it spans.
+fn split_lines(text: &ParsedNasupRichText) -> Vec<Vec<ParsedNasupTextRun>> {
+  let mut lines = vec![Vec::new()];
+  for run in &text.runs {
+    let run_text = run.text.replace("\r\n", "\n");
+    for (i, part) in run_text.split('\n').enumerate() {
+      if i > 0 {
+        lines.push(Vec::new());
+      }
+      if !part.is_empty() {
+        lines.last_mut().unwrap().push(ParsedNasupTextRun {
+          text: part.to_owned(),
+          ..run.clone()
+        });
+      }
+    }
+  }
+  lines
+}
+
+/// Whether a line is an ordered or unordered list item, and the length in
+/// bytes of its marker and any whitespace around it.
+fn list_marker(line: &str) -> Option<(bool, usize)> {
+  let trimmed = line.trim_start();
+
+  let unordered = ['-', '*', '•', '–', '—']
+    .into_iter()
+    .find_map(|bullet| trimmed.strip_prefix(bullet));
+  let ordered = trimmed
+    .find(|c: char| !c.is_ascii_digit())
+    .filter(|digits| (1..=3).contains(digits))
+    .and_then(|digits| {
+      trimmed[digits..]
+        .strip_prefix('.')
+        .or_else(|| trimmed[digits..].strip_prefix(')'))
+    });
+
+  let (ordered, rest) = match (unordered, ordered) {
+    (Some(rest), _) => (false, rest),
+    (None, Some(rest)) => (true, rest),
+    (None, None) => return None,
+  };
+  // "-5 degrees" and "3.5 hours" aren't list items
+  if !rest.starts_with(char::is_whitespace) {
+    return None;
+  }
+  Some((ordered, line.len() - rest.trim_start().len()))
+}
+
+fn strip_prefix_bytes(
+  runs: Vec<ParsedNasupTextRun>,
+  mut len: usize,
+) -> Vec<ParsedNasupTextRun> {
+  runs
+    .into_iter()
+    .filter_map(|mut run| {
+      let cut = len.min(run.text.len());
+      len -= cut;
+      run.text.drain(..cut);
+      (!run.text.is_empty()).then_some(run)
+    })
+    .collect()
+}
+
+fn trim_runs(runs: Vec<ParsedNasupTextRun>) -> Vec<ParsedNasupTextRun> {
+  ParsedNasupRichText { runs }.trim().runs
+}
+
+/// Renders the runs of a line, joining neighbors with the same formatting
+/// first so that links aren't split between them.
+fn render_runs(runs: &[ParsedNasupTextRun]) -> String {
+  let mut joined: Vec<ParsedNasupTextRun> = Vec::new();
+  for run in runs {
+    match joined.last_mut() {
+      Some(last) if last.bold == run.bold && last.italic == run.italic => {
+        last.text.push_str(&run.text);
+      }
+      _ => joined.push(run.clone()),
+    }
+  }
+
+  joined
+    .iter()
+    .map(|run| {
+      let mut html = render_text(&run.text);
+      if run.italic {
+        html = format!("<i>{html}</i>");
+      }
+      if run.bold {
+        html = format!("<b>{html}</b>");
+      }
+      html
+    })
+    .collect()
+}
+
+/// Escapes text, turning bare URLs and emails into links.
+fn render_text(text: &str) -> String {
+  text
+    .split_inclusive(char::is_whitespace)
+    .map(|piece| {
+      let word = piece.trim_end_matches(char::is_whitespace);
+      let whitespace = &piece[word.len()..];
+
+      let inner = word.trim_start_matches(['(', '"', '\'', '<']);
+      let leading = &word[..word.len() - inner.len()];
+      let mut link = inner
+        .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '"', '\'', '>']);
+      // keep a closing parenthesis that belongs to the URL, as on Wikipedia
+      if link.contains('(') && inner[link.len()..].starts_with(')') {
+        link = &inner[..link.len() + 1];
+      }
+      let trailing = &inner[link.len()..];
+
+      match link_href(link) {
+        Some(href) => format!(
+          "{leading}<a href=\"{href}\">{link}</a>{trailing}{whitespace}",
+          leading = html_escape::encode_text(leading),
+          href = html_escape::encode_double_quoted_attribute(&href),
+          link = html_escape::encode_text(link),
+          trailing = html_escape::encode_text(trailing),
+        ),
+        None => html_escape::encode_text(piece).into_owned(),
+      }
+    })
+    .collect()
+}
+
+fn link_href(word: &str) -> Option<String> {
+  let lowercase = word.to_lowercase();
+  if let Some(rest) = ["https://", "http://"]
+    .into_iter()
+    .find_map(|scheme| lowercase.strip_prefix(scheme))
+  {
+    return (!rest.is_empty()).then(|| word.to_owned());
+  }
+  if let Some(rest) = lowercase.strip_prefix("www.") {
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    return is_domain(host).then(|| format!("https://{word}"));
+  }
+
+  let (local, domain) = word.split_once('@')?;
+  let is_local_char = |c: char| c.is_alphanumeric() || "._%+-".contains(c);
+  (!local.is_empty() && local.chars().all(is_local_char) && is_domain(domain))
+    .then(|| format!("mailto:{word}"))
+}
+
+fn is_domain(host: &str) -> bool {
+  host.contains('.')
+    && !host.starts_with('.')
+    && !host.ends_with('.')
+    && host
+      .chars()
+      .all(|c| c.is_alphanumeric() || c == '.' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn render(text: &str) -> String {
+    render_description_html(&ParsedNasupRichText::plain(text))
+  }
+
+  #[test]
+  fn renders_lines_as_paragraphs_and_lists() {
+    assert_eq!(
+      render("Intro\n- one\n* two\n\n1. first\n2) second"),
+      "<p>Intro</p><ul><li>one</li><li>two</li></ul><ol><li>first</\
+       li><li>second</li></ol>"
+    );
+  }
+
+  #[test]
+  fn doesnt_mistake_numbers_for_list_markers() {
+    assert_eq!(render("-5 degrees"), "<p>-5 degrees</p>");
+    assert_eq!(render("3.5 hours"), "<p>3.5 hours</p>");
+    assert_eq!(render("2024 was long"), "<p>2024 was long</p>");
+  }
+
+  #[test]
+  fn separates_lists_at_blank_lines() {
+    assert_eq!(
+      render("- one\n\n- two"),
+      "<ul><li>one</li></ul><ul><li>two</li></ul>"
+    );
+  }
+
+  #[test]
+  fn keeps_bold_and_italic_runs() {
+    let text = ParsedNasupRichText {
+      runs: vec![
+        ParsedNasupTextRun {
+          text:   "Read ".to_owned(),
+          bold:   false,
+          italic: false,
+        },
+        ParsedNasupTextRun {
+          text:   "this".to_owned(),
+          bold:   true,
+          italic: true,
+        },
+      ],
+    };
+
+    assert_eq!(
+      render_description_html(&text),
+      "<p>Read <b><i>this</i></b></p>"
+    );
+  }
+
+  #[test]
+  fn links_urls_and_emails() {
+    assert_eq!(
+      render("See https://example.org/a?b=c&d, or www.example.org."),
+      "<p>See <a href=\"https://example.org/a?b=c&amp;d\">\
+       https://example.org/a?b=c&amp;d</a>, or <a \
+       href=\"https://www.example.org\">www.example.org</a>.</p>"
+    );
+    assert_eq!(
+      render("Email (jo.doe@example.edu)"),
+      "<p>Email (<a \
+       href=\"mailto:jo.doe@example.edu\">jo.doe@example.edu</a>)</p>"
+    );
+    assert_eq!(
+      render("https://en.wikipedia.org/wiki/Rust_(language)"),
+      "<p><a href=\"https://en.wikipedia.org/wiki/Rust_(language)\">\
+       https://en.wikipedia.org/wiki/Rust_(language)</a></p>"
+    );
+  }
+
+  #[test]
+  fn doesnt_link_lookalikes() {
+    assert_eq!(render("@handle and a@b"), "<p>@handle and a@b</p>");
+    assert_eq!(render("www.nodomain"), "<p>www.nodomain</p>");
+    assert_eq!(render("<b>x</b>"), "<p>&lt;b&gt;x&lt;/b&gt;</p>");
+  }
+
+  #[test]
+  fn leaves_short_descriptions_alone() {
+    let html = "<p>short</p>".to_owned();
+
+    assert_eq!(limit_description_html(html.clone(), 20, "test"), html);
+  }
+
+  #[test]
+  fn truncates_at_the_last_block_that_fits() {
+    let html = "<p>ééé</p><ul><li>a</li></ul><p>too long</p>".to_owned();
+
+    // the marker takes 8 characters, leaving room for the first two blocks
+    assert_eq!(
+      limit_description_html(html, 10 + 19 + 8, "test"),
+      "<p>ééé</p><ul><li>a</li></ul><p>…</p>"
+    );
+  }
+
+  #[test]
+  fn truncates_to_the_marker_when_no_block_fits() {
+    let html = "<p>far too long</p>".to_owned();
+
+    assert_eq!(limit_description_html(html, 12, "test"), "<p>…</p>");
+  }
+
+  #[test]
+  fn keeps_what_the_template_adds_around_a_long_body() {
+    let template =
+      Template::parse("{{{body}}}<p>Intended for: {{audience}}</p>").unwrap();
+    let paragraph = format!("<p>{}</p>", "x".repeat(993));
+    let context = serde_json::json!({
+      "body": paragraph.repeat(30),
+      "audience": "Faculty",
+    });
+
+    let html =
+      render_limited_description_html(&template, context, "body", "test");
+
+    assert!(html.chars().count() <= DESCRIPTION_HTML_LIMIT);
+    assert!(html.ends_with("<p>…</p><p>Intended for: Faculty</p>"));
+    assert!(html.starts_with(&paragraph.repeat(19)));
+  }
+}
@@ -0,0 +1,305 @@
+use miette::{Context, IntoDiagnostic, bail};
+use serde::{Serialize, de::DeserializeOwned};
+use tracing::{debug, error, info, instrument, warn};
+
+use crate::{
+  config::Config,
+  guidebook::{
+    Modification, delete_guidebook_entity, fetch_guidebook_entity,
+    model::{
+      GuidebookContentType, GuidebookCustomList, GuidebookCustomListItem,
+      GuidebookLink, GuidebookLocation, GuidebookScheduleTrack,
+      GuidebookSession,
+    },
+    upsert_guidebook_entity,
+  },
+  store::{Mutation, MutationKind, PendingMutation},
+};
+
+/// Reverses every mutation journaled under the given run, newest first.
+///
+/// Created entities are deleted, updated entities have the fields that were
+/// patched restored to their prior values, and deleted entities are recreated
+/// from the writable fields of their last known state. Recreated entities get
+/// new IDs, so anything that referred to them by ID (e.g. links) can't be
+/// restored this way.
+///
+/// Mutations left pending, because the run stopped before their responses
+/// were journaled, are reversed first where that's safe whether or not they
+/// were applied. Pending creations can't be, since the ID of anything they
+/// created is unknown.
+#[instrument(skip(config))]
+pub async fn rollback_run(config: &Config, run_id: i64) -> miette::Result<()> {
+  let mutations = config
+    .store
+    .run_mutations(run_id)
+    .context("failed to read run mutations from store")?;
+  let pending_mutations = config
+    .store
+    .run_pending_mutations(run_id)
+    .context("failed to read pending run mutations from store")?;
+  info!(
+    count = mutations.len(),
+    pending_count = pending_mutations.len(),
+    "rolling back run mutations"
+  );
+
+  let mut failures = 0;
+  for mutation in pending_mutations.into_iter().rev() {
+    if let Err(e) = rollback_pending_mutation(config, &mutation).await {
+      error!(
+        kind = %mutation.kind,
+        endpoint = mutation.endpoint,
+        entity_id = mutation.entity_id,
+        request = ?mutation.request,
+        "failed to roll back pending mutation: {e:?}"
+      );
+      failures += 1;
+    }
+  }
+  for mutation in mutations.into_iter().rev() {
+    if let Err(e) = rollback_mutation(config, &mutation).await {
+      error!(
+        kind = %mutation.kind,
+        endpoint = mutation.endpoint,
+        entity_id = mutation.entity_id,
+        "failed to roll back mutation: {e:?}"
+      );
+      failures += 1;
+    }
+  }
+
+  if failures > 0 {
+    miette::bail!("failed to roll back {failures} mutations of run {run_id}");
+  }
+
+  Ok(())
+}
+
+async fn rollback_mutation(
+  config: &Config,
+  mutation: &Mutation,
+) -> miette::Result<()> {
+  match mutation.kind {
+    MutationKind::Create => {
+      debug!(
+        endpoint = mutation.endpoint,
+        entity_id = mutation.entity_id,
+        "deleting created entity"
+      );
+      delete_guidebook_entity(
+        config,
+        &mutation.endpoint,
+        mutation.entity_id,
+        None,
+      )
+      .await
+      .context("failed to delete created entity")?;
+    }
+    MutationKind::Update => {
+      let (Some(request), Some(before)) = (&mutation.request, &mutation.before)
+      else {
+        bail!("journaled update is missing its request or prior state");
+      };
+      restore_updated_fields(
+        config,
+        &mutation.endpoint,
+        mutation.entity_id,
+        request,
+        before,
+      )
+      .await?;
+    }
+    MutationKind::Delete => {
+      let Some(before) = &mutation.before else {
+        bail!("journaled deletion is missing its prior state");
+      };
+      recreate_deleted_entity(
+        config,
+        &mutation.endpoint,
+        mutation.entity_id,
+        before,
+      )
+      .await?;
+    }
+  }
+
+  Ok(())
+}
+
+async fn rollback_pending_mutation(
+  config: &Config,
+  mutation: &PendingMutation,
+) -> miette::Result<()> {
+  warn!(
+    kind = %mutation.kind,
+    endpoint = mutation.endpoint,
+    entity_id = mutation.entity_id,
+    "found mutation that may or may not have been applied"
+  );
+
+  match (mutation.kind, mutation.entity_id) {
+    (MutationKind::Update, Some(entity_id)) => {
+      let (Some(request), Some(before)) = (&mutation.request, &mutation.before)
+      else {
+        bail!("journaled update is missing its request or prior state");
+      };
+      // restoring fields that were never changed is harmless
+      restore_updated_fields(
+        config,
+        &mutation.endpoint,
+        entity_id,
+        request,
+        before,
+      )
+      .await?;
+    }
+    (MutationKind::Delete, Some(entity_id)) => {
+      let Some(before) = &mutation.before else {
+        bail!("journaled deletion is missing its prior state");
+      };
+      let still_exists = fetch_guidebook_entity::<serde_json::Value>(
+        config,
+        &mutation.endpoint,
+        entity_id,
+      )
+      .await
+      .is_ok();
+      if still_exists {
+        info!(
+          endpoint = mutation.endpoint,
+          entity_id, "pending deletion was never applied, skipping"
+        );
+        return Ok(());
+      }
+      recreate_deleted_entity(config, &mutation.endpoint, entity_id, before)
+        .await?;
+    }
+    _ => bail!(
+      "can't tell whether a pending {kind} under {endpoint} was applied, so \
+       check for it in the guidebook builder",
+      kind = mutation.kind,
+      endpoint = mutation.endpoint,
+    ),
+  }
+
+  Ok(())
+}
+
+/// Restores only the fields that an update actually sent, apart from uploaded
+/// images, which can't be sent back as JSON.
+async fn restore_updated_fields(
+  config: &Config,
+  endpoint: &str,
+  entity_id: u32,
+  request: &serde_json::Value,
+  before: &serde_json::Value,
+) -> miette::Result<()> {
+  let restore_payload = request
+    .as_object()
+    .into_iter()
+    .flat_map(|o| o.keys())
+    .filter(|k| !matches!(k.as_str(), "id" | "guide" | "image"))
+    .map(|k| (k.clone(), before[k].clone()))
+    .collect::<serde_json::Map<_, _>>();
+  if restore_payload.is_empty() {
+    warn!(
+      endpoint,
+      entity_id, "journaled update changed no fields, skipping"
+    );
+    return Ok(());
+  }
+
+  debug!(
+    endpoint,
+    entity_id, "restoring prior fields of updated entity"
+  );
+  upsert_guidebook_entity(
+    config,
+    serde_json::Value::Object(restore_payload),
+    endpoint,
+    Modification::Update {
+      id:     entity_id,
+      before: None,
+    },
+  )
+  .await
+  .context("failed to restore prior fields of updated entity")?;
+
+  Ok(())
+}
+
+async fn recreate_deleted_entity(
+  config: &Config,
+  endpoint: &str,
+  entity_id: u32,
+  before: &serde_json::Value,
+) -> miette::Result<()> {
+  let payload = recreation_payload(endpoint, before)
+    .context("failed to rebuild deleted entity from its prior state")?;
+
+  debug!(endpoint, entity_id, "recreating deleted entity");
+  let recreated = upsert_guidebook_entity(
+    config,
+    payload,
+    &format!("{endpoint}/"),
+    Modification::Create,
+  )
+  .await
+  .context("failed to recreate deleted entity")?;
+  warn!(
+    endpoint,
+    old_id = entity_id,
+    new_id = ?recreated["id"],
+    "recreated deleted entity under a new ID"
+  );
+
+  Ok(())
+}
+
+/// Rebuilds the creation payload of a deleted entity from the writable fields
+/// of its prior state, by reading it as the model of its endpoint, so that
+/// fields Guidebook owns aren't sent back.
+fn recreation_payload(
+  endpoint: &str,
+  before: &serde_json::Value,
+) -> miette::Result<serde_json::Value> {
+  fn writable_fields<T: Serialize + DeserializeOwned>(
+    before: &serde_json::Value,
+  ) -> miette::Result<serde_json::Value> {
+    let entity = serde_json::from_value::<T>(before.clone())
+      .into_diagnostic()
+      .context("failed to read prior state of deleted entity")?;
+    let mut payload = serde_json::to_value(entity)
+      .into_diagnostic()
+      .context("failed to serialize deleted entity")?;
+    if let Some(fields) = payload.as_object_mut() {
+      fields.remove("id");
+    }
+    Ok(payload)
+  }
+
+  match endpoint {
+    "/links" => {
+      let link = serde_json::from_value::<GuidebookLink>(before.clone())
+        .into_diagnostic()
+        .context("failed to read prior state of deleted link")?;
+      if [link.source_content_type, link.target_content_type]
+        .contains(&GuidebookContentType::Other)
+      {
+        bail!("can't recreate a link to content this tool doesn't manage");
+      }
+      writable_fields::<GuidebookLink>(before)
+    }
+    "/sessions" => writable_fields::<GuidebookSession>(before),
+    "/schedule-tracks" => writable_fields::<GuidebookScheduleTrack>(before),
+    "/locations" => writable_fields::<GuidebookLocation>(before),
+    "/custom-list-items" => writable_fields::<GuidebookCustomListItem>(before),
+    "/custom-lists" => writable_fields::<GuidebookCustomList>(before),
+    "/custom-list-item-relations" => Ok(serde_json::json!({
+      "custom_list": before["custom_list"],
+      "custom_list_item": before["custom_list_item"],
+    })),
+    endpoint => bail!("don't know the writable fields of {endpoint} entities"),
+  }
+}
@@ -0,0 +1,356 @@
+use std::{collections::HashMap, path::Path};
+
+use chrono::{DateTime, Utc};
+use miette::{Context, IntoDiagnostic, bail};
+use serde::Deserialize;
+
+use crate::{
+  config::{INSTITUTION_LIST_KEY, PRESENTER_LIST_KEY},
+  templates::TemplateSettings,
+  track_colors::TrackColorSettings,
+};
+
+/// Tunables for how spreadsheet data is turned into Guidebook entities.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+  #[serde(default)]
+  pub track_colors:        TrackColorSettings,
+  /// Whether each intended audience becomes its own schedule track, so the
+  /// schedule can be filtered by audience.
+  #[serde(default)]
+  pub audience_tracks:     bool,
+  /// The room registry, keyed by canonical room name.
+  #[serde(default)]
+  pub rooms:               HashMap<String, RoomSettings>,
+  /// What separates rooms in a room cell naming several rooms.
+  #[serde(default = "default_room_separators")]
+  pub room_separators:     Vec<String>,
+  /// Registration policies, keyed by session type name. Sessions of other
+  /// types can be added to schedules without logging in.
+  #[serde(default)]
+  pub registration:        HashMap<String, RegistrationPolicy>,
+  #[serde(default)]
+  pub templates:           TemplateSettings,
+  /// Whether session descriptions list their presenters.
+  pub presenter_roster:    Option<PresenterRosterSettings>,
+  /// The name `bootstrap` finds or creates the presenter custom list by.
+  #[serde(default = "default_presenter_list_name")]
+  pub presenter_list_name: String,
+  /// An optional custom list of the presenters' institutions.
+  pub institutions:        Option<InstitutionListSettings>,
+  /// An optional worksheet of breaks, meals, keynotes and other agenda items
+  /// that aren't submitted sessions.
+  pub agenda:              Option<AgendaSettings>,
+  /// Custom lists synced from their own worksheets, keyed by a short name
+  /// that namespaces their item import IDs.
+  #[serde(default)]
+  pub custom_lists:        HashMap<String, CustomListSettings>,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Self {
+      track_colors:        TrackColorSettings::default(),
+      audience_tracks:     false,
+      rooms:               HashMap::new(),
+      room_separators:     default_room_separators(),
+      registration:        HashMap::new(),
+      templates:           TemplateSettings::default(),
+      presenter_roster:    None,
+      presenter_list_name: default_presenter_list_name(),
+      institutions:        None,
+      agenda:              None,
+      custom_lists:        HashMap::new(),
+    }
+  }
+}
+
+fn default_presenter_list_name() -> String { "Presenters".to_owned() }
+
+fn default_room_separators() -> Vec<String> {
+  ["/", "&", ";"].map(ToOwned::to_owned).to_vec()
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoomSettings {
+  /// Other names the sessions spreadsheet uses for this room.
+  #[serde(default)]
+  pub aliases:       Vec<String>,
+  /// The name shown in Guidebook, if it differs from the canonical name.
+  pub name:          Option<String>,
+  pub building:      Option<String>,
+  pub floor:         Option<String>,
+  /// How many people fit in the room.
+  pub capacity:      Option<u32>,
+  #[serde(default)]
+  pub location_type: LocationType,
+  pub latitude:      Option<f64>,
+  pub longitude:     Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PresenterRosterSettings {
+  /// Whether presenters that haven't paid are listed too, as co-authors.
+  #[serde(default)]
+  pub include_co_authors: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgendaSettings {
+  pub spreadsheet_id: String,
+  #[serde(default = "default_agenda_name")]
+  pub worksheet:      String,
+  /// The name of the schedule track every agenda item is in.
+  #[serde(default = "default_agenda_name")]
+  pub track_name:     String,
+}
+
+fn default_agenda_name() -> String { "Agenda".to_owned() }
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstitutionListSettings {
+  /// The Guidebook ID of the custom list. Without one, `bootstrap` finds or
+  /// creates the list by name.
+  pub list_id: Option<u32>,
+  #[serde(default = "default_institution_list_name")]
+  pub name:    String,
+  /// Other names the presenter spreadsheet uses for an institution, keyed by
+  /// its canonical name.
+  #[serde(default)]
+  pub aliases: HashMap<String, Vec<String>>,
+}
+
+fn default_institution_list_name() -> String { "Institutions".to_owned() }
+
+impl InstitutionListSettings {
+  /// The canonical name of an institution, by one of its aliases, ignoring
+  /// case and whitespace. Institutions without aliases keep their name.
+  pub fn canonical_name(&self, name: &str) -> String {
+    let key = name_key(name);
+    self
+      .aliases
+      .iter()
+      .find(|(institution, aliases)| {
+        Some(*institution)
+          .into_iter()
+          .chain(*aliases)
+          .any(|n| name_key(n) == key)
+      })
+      .map_or(name.trim().to_owned(), |(institution, _)| {
+        institution.clone()
+      })
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomListSettings {
+  /// The Guidebook ID of the custom list. Without one, `bootstrap` finds or
+  /// creates the list by name.
+  pub list_id:         Option<u32>,
+  /// The name of the custom list in Guidebook, defaulting to its key.
+  pub name:            Option<String>,
+  pub spreadsheet_id:  String,
+  pub worksheet:       String,
+  pub columns:         CustomListColumns,
+  /// The header of a column naming the sessions each item links to, by title
+  /// and separated by semicolons or line breaks. Without one, links to the
+  /// list's items are left alone.
+  pub sessions_column: Option<String>,
+}
+
+/// The headers of the columns each custom list item field comes from. Fields
+/// without a column are left to the builder.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomListColumns {
+  /// The item name, which also identifies the item.
+  pub name:             String,
+  pub subtitle:         Option<String>,
+  pub description_html: Option<String>,
+  pub contact_email:    Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegistrationPolicy {
+  /// Whether attendees must log in to add sessions to their schedule.
+  #[serde(default)]
+  pub require_login:           bool,
+  /// Whether seats are limited, to the capacity column or else the combined
+  /// capacity of the session's rooms.
+  #[serde(default)]
+  pub limit_capacity:          bool,
+  /// Whether full sessions have a waitlist.
+  #[serde(default)]
+  pub waitlist:                bool,
+  pub registration_start_date: Option<DateTime<Utc>>,
+  pub registration_end_date:   Option<DateTime<Utc>>,
+}
+
+impl RegistrationPolicy {
+  /// Checks the prerequisites Guidebook has between registration fields.
+  fn validate(&self) -> miette::Result<()> {
+    if self.waitlist && !self.limit_capacity {
+      bail!("a waitlist requires `limit_capacity`");
+    }
+    if self.limit_capacity && !self.require_login {
+      bail!("limiting capacity requires `require_login`");
+    }
+    if (self.registration_start_date.is_some()
+      || self.registration_end_date.is_some())
+      && !self.require_login
+    {
+      bail!("registration dates require `require_login`");
+    }
+    if let (Some(start), Some(end)) =
+      (self.registration_start_date, self.registration_end_date)
+      && start >= end
+    {
+      bail!("registration must start before it ends");
+    }
+
+    Ok(())
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationType {
+  MainVenue,
+  #[default]
+  Placeholder,
+  GoogleMaps,
+}
+
+impl LocationType {
+  /// The value of Guidebook's `location_type` field.
+  pub fn guidebook_id(&self) -> u32 {
+    match self {
+      LocationType::MainVenue => 1,
+      LocationType::Placeholder => 2,
+      LocationType::GoogleMaps => 3,
+    }
+  }
+}
+
+impl Settings {
+  pub fn from_file(path: &Path) -> miette::Result<Self> {
+    let contents = std::fs::read_to_string(path)
+      .into_diagnostic()
+      .context(format!("failed to read settings file at {path:?}"))?;
+    let jd = &mut serde_json::Deserializer::from_str(&contents);
+    let settings: Settings = serde_path_to_error::deserialize(jd)
+      .into_diagnostic()
+      .context("failed to parse settings file")?;
+
+    settings
+      .track_colors
+      .validate()
+      .context("found invalid track color settings")?;
+
+    let main_venues = settings
+      .rooms
+      .values()
+      .filter(|r| r.location_type == LocationType::MainVenue)
+      .count();
+    if main_venues > 1 {
+      bail!("found {main_venues} main venue rooms, but a guide has only one");
+    }
+    for (room, room_settings) in &settings.rooms {
+      if room_settings.location_type == LocationType::GoogleMaps
+        && (room_settings.latitude.is_none()
+          || room_settings.longitude.is_none())
+      {
+        bail!("google maps room {room:?} needs a latitude and longitude");
+      }
+    }
+
+    for (session_type, policy) in &settings.registration {
+      policy.validate().context(format!(
+        "found invalid registration policy for {session_type:?}"
+      ))?;
+    }
+
+    for reserved in [PRESENTER_LIST_KEY, INSTITUTION_LIST_KEY] {
+      if settings.custom_lists.contains_key(reserved) {
+        bail!("custom list key {reserved:?} is reserved");
+      }
+    }
+    let mut lists_by_id = HashMap::new();
+    for (list, list_settings) in &settings.custom_lists {
+      let Some(list_id) = list_settings.list_id else {
+        continue;
+      };
+      if let Some(other) = lists_by_id.insert(list_id, list) {
+        bail!(
+          "custom lists {other:?} and {list:?} both sync into list {list_id}"
+        );
+      }
+    }
+
+    if let Some(institutions) = &settings.institutions {
+      let mut institutions_by_key = HashMap::new();
+      for (institution, aliases) in &institutions.aliases {
+        for name in Some(institution).into_iter().chain(aliases) {
+          if let Some(other) =
+            institutions_by_key.insert(name_key(name), institution)
+            && other != institution
+          {
+            bail!(
+              "institution name {name:?} refers to both {other:?} and \
+               {institution:?}"
+            );
+          }
+        }
+      }
+    }
+
+    let mut rooms_by_key = HashMap::new();
+    for (room, room_settings) in &settings.rooms {
+      for name in Some(room).into_iter().chain(&room_settings.aliases) {
+        if let Some(other) = rooms_by_key.insert(name_key(name), room)
+          && other != room
+        {
+          bail!("room name {name:?} refers to both {other:?} and {room:?}");
+        }
+      }
+    }
+
+    Ok(settings)
+  }
+
+  /// Finds the room a spreadsheet room name refers to, by its canonical name
+  /// or one of its aliases, ignoring case and whitespace.
+  pub fn resolve_room(&self, name: &str) -> Option<(&str, &RoomSettings)> {
+    let key = name_key(name);
+    self.rooms.iter().find_map(|(room, room_settings)| {
+      Some(room)
+        .into_iter()
+        .chain(&room_settings.aliases)
+        .any(|n| name_key(n) == key)
+        .then_some((room.as_str(), room_settings))
+    })
+  }
+
+  /// The canonical name of a spreadsheet room name, which is the name itself
+  /// for rooms that aren't in the registry.
+  pub fn canonical_room_name(&self, name: &str) -> String {
+    self
+      .resolve_room(name)
+      .map_or(name.to_owned(), |(room, _)| room.to_owned())
+  }
+}
+
+fn name_key(name: &str) -> String {
+  name
+    .split_whitespace()
+    .collect::<Vec<_>>()
+    .join(" ")
+    .to_lowercase()
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use kinded::Kinded;
 use miette::Context;
@@ -10,25 +10,36 @@ use crate::{
   guidebook::{
     fetch_all_guidebook_entities,
     model::{
-      GuidebookLocation, GuidebookPresenter, GuidebookScheduleTrack,
-      GuidebookSession,
+      GuidebookCustomListItem, GuidebookLocation, GuidebookPresenter,
+      GuidebookScheduleTrack, GuidebookSession,
     },
   },
   nasup_to_guidebook::{
-    WithLinks, nasup_session_to_guidebook_session,
+    LinksByImportId, nasup_agenda_items_to_guidebook_sessions,
     nasup_sessions_to_guidebook_locations,
     nasup_sessions_to_guidebook_presenters,
     nasup_sessions_to_guidebook_schedule_tracks,
+    nasup_sessions_to_guidebook_sessions,
+    nasup_sessions_to_presenter_headshots,
   },
   parse_nasup::{
+    parse_agenda_items::parse_nasup_agenda_items_from_worksheet,
     parse_model::{
-      ParsedNasupPresenterWithInstitutionBySession, ParsedNasupSession,
-      ParsedNasupStrandAndIntendedAudience,
+      ParsedNasupAgendaItem, ParsedNasupPresenterWithInstitutionBySession,
+      ParsedNasupSession, ParsedNasupStrandAndIntendedAudience,
+      ParsedNasupStrandMetadata,
     },
     parse_presenter_institutions::parse_nasup_presenter_institutions_from_worksheet,
     parse_sessions::parse_nasup_sessions_from_worksheet,
+    parse_strand_metadata::parse_nasup_strand_metadata_from_worksheet,
     parse_strands::parse_nasup_strands_from_worksheet,
   },
+  reconcile_guidebook_custom_lists::{
+    CustomListReconciliation, IntendedCustomList,
+    fetch_existing_custom_list_items, fetch_intended_custom_lists,
+    link_custom_list_items_to_sessions, plan_institution_links,
+    reconcile_intended_and_existing_guidebook_custom_list_items,
+  },
   reconcile_guidebook_locations::{
     LocationsReconciliation,
     reconcile_intended_and_existing_guidebook_locations,
@@ -44,8 +55,17 @@ use crate::{
     StrandsReconciliation,
     reconcile_intended_and_existing_guidebook_schedule_tracks,
   },
-  synchronize_links::synchronize_session_links,
-  synth_nasup::{NasupSession, synthesize_parsed_nasup_data},
+  store::SyncedEntityKind,
+  synchronize_headshots::synchronize_presenter_headshots,
+  synchronize_links::{
+    IntendedSessionLinks, LinkScope, resolve_intended_session_links,
+    synchronize_session_links,
+  },
+  synth_nasup::{
+    NasupAgendaItem, NasupSession, synthesize_parsed_nasup_agenda_items,
+    synthesize_parsed_nasup_data,
+  },
+  verify_convergence::verify_convergence,
 };
 
 #[derive(Kinded)]
@@ -53,49 +73,61 @@ use crate::{
 pub enum MasterState {
   Start,
   FetchedSheets {
-    sessions_worksheet:  DecodedWorksheet,
-    presenter_worksheet: DecodedWorksheet,
-    strands_worksheet:   DecodedWorksheet,
+    sessions_worksheet:        DecodedWorksheet,
+    presenter_worksheet:       DecodedWorksheet,
+    strands_worksheet:         DecodedWorksheet,
+    strand_metadata_worksheet: Option<DecodedWorksheet>,
+    agenda_worksheet:          Option<DecodedWorksheet>,
   },
   ParsedInputs {
-    sessions:   Vec<ParsedNasupSession>,
-    presenters: Vec<ParsedNasupPresenterWithInstitutionBySession>,
-    strands:    Vec<ParsedNasupStrandAndIntendedAudience>,
+    sessions:        Vec<ParsedNasupSession>,
+    presenters:      Vec<ParsedNasupPresenterWithInstitutionBySession>,
+    strands:         Vec<ParsedNasupStrandAndIntendedAudience>,
+    strand_metadata: Vec<ParsedNasupStrandMetadata>,
+    agenda_items:    Vec<ParsedNasupAgendaItem>,
   },
   SynthesizedInputs {
-    sessions: Vec<NasupSession>,
+    sessions:     Vec<NasupSession>,
+    agenda_items: Vec<NasupAgendaItem>,
   },
   FetchedStrandsState {
     sessions:         Vec<NasupSession>,
+    agenda_items:     Vec<NasupAgendaItem>,
     intended_strands: Vec<GuidebookScheduleTrack>,
     existing_strands: Vec<GuidebookScheduleTrack>,
   },
   CalculatedStrandsReconciliation {
     sessions:               Vec<NasupSession>,
+    agenda_items:           Vec<NasupAgendaItem>,
     strands_reconciliation: StrandsReconciliation,
   },
   ExecutedStrandsReconciliation {
     sessions:         Vec<NasupSession>,
+    agenda_items:     Vec<NasupAgendaItem>,
     existing_strands: Vec<GuidebookScheduleTrack>,
   },
   FetchedLocationState {
     sessions:           Vec<NasupSession>,
+    agenda_items:       Vec<NasupAgendaItem>,
     existing_strands:   Vec<GuidebookScheduleTrack>,
     intended_locations: Vec<GuidebookLocation>,
     existing_locations: Vec<GuidebookLocation>,
   },
   CalculatedLocationReconciliation {
     sessions:                 Vec<NasupSession>,
+    agenda_items:             Vec<NasupAgendaItem>,
     existing_strands:         Vec<GuidebookScheduleTrack>,
     locations_reconciliation: LocationsReconciliation,
   },
   ExecutedLocationReconciliation {
     sessions:           Vec<NasupSession>,
+    agenda_items:       Vec<NasupAgendaItem>,
     existing_strands:   Vec<GuidebookScheduleTrack>,
     existing_locations: Vec<GuidebookLocation>,
   },
   FetchedGuidebookPresenterState {
     sessions:            Vec<NasupSession>,
+    agenda_items:        Vec<NasupAgendaItem>,
     existing_strands:    Vec<GuidebookScheduleTrack>,
     existing_locations:  Vec<GuidebookLocation>,
     intended_presenters: Vec<GuidebookPresenter>,
@@ -103,111 +135,206 @@ pub enum MasterState {
   },
   CalculatedPresenterReconciliation {
     sessions:                 Vec<NasupSession>,
+    agenda_items:             Vec<NasupAgendaItem>,
     existing_strands:         Vec<GuidebookScheduleTrack>,
     existing_locations:       Vec<GuidebookLocation>,
     presenter_reconciliation: PresenterReconciliation,
   },
   ExecutedPresenterReconciliation {
     sessions:            Vec<NasupSession>,
+    agenda_items:        Vec<NasupAgendaItem>,
     existing_strands:    Vec<GuidebookScheduleTrack>,
     existing_locations:  Vec<GuidebookLocation>,
     existing_presenters: Vec<GuidebookPresenter>,
   },
+  FetchedCustomListState {
+    sessions:                   Vec<NasupSession>,
+    agenda_items:               Vec<NasupAgendaItem>,
+    existing_strands:           Vec<GuidebookScheduleTrack>,
+    existing_locations:         Vec<GuidebookLocation>,
+    existing_presenters:        Vec<GuidebookPresenter>,
+    intended_custom_lists:      Vec<IntendedCustomList>,
+    existing_custom_list_items: HashMap<u32, Vec<GuidebookCustomListItem>>,
+  },
+  CalculatedCustomListReconciliation {
+    sessions:                    Vec<NasupSession>,
+    agenda_items:                Vec<NasupAgendaItem>,
+    existing_strands:            Vec<GuidebookScheduleTrack>,
+    existing_locations:          Vec<GuidebookLocation>,
+    existing_presenters:         Vec<GuidebookPresenter>,
+    intended_custom_lists:       Vec<IntendedCustomList>,
+    custom_list_reconciliations: Vec<CustomListReconciliation>,
+  },
+  ExecutedCustomListReconciliation {
+    sessions:                   Vec<NasupSession>,
+    agenda_items:               Vec<NasupAgendaItem>,
+    existing_strands:           Vec<GuidebookScheduleTrack>,
+    existing_locations:         Vec<GuidebookLocation>,
+    existing_presenters:        Vec<GuidebookPresenter>,
+    intended_custom_lists:      Vec<IntendedCustomList>,
+    existing_custom_list_items: HashMap<u32, Vec<GuidebookCustomListItem>>,
+  },
   FetchedGuidebookSessionState {
+    sessions: Vec<NasupSession>,
+    agenda_items: Vec<NasupAgendaItem>,
+    link_scope: LinkScope,
     intended_sessions: Vec<GuidebookSession>,
     existing_sessions: Vec<GuidebookSession>,
-    intended_session_import_id_to_presenter_link_map: HashMap<String, Vec<u32>>,
+    intended_session_links_by_import_id: LinksByImportId,
   },
   CalculatedSessionReconciliation {
+    sessions: Vec<NasupSession>,
+    agenda_items: Vec<NasupAgendaItem>,
+    link_scope: LinkScope,
     session_reconciliation: SessionReconciliation,
-    intended_session_import_id_to_presenter_link_map: HashMap<String, Vec<u32>>,
+    intended_session_links_by_import_id: LinksByImportId,
   },
   ExecutedSessionReconciliation {
-    intended_session_to_presenter_link_map: HashMap<u32, Vec<u32>>,
+    sessions:               Vec<NasupSession>,
+    agenda_items:           Vec<NasupAgendaItem>,
+    link_scope:             LinkScope,
+    intended_session_links: HashMap<u32, IntendedSessionLinks>,
   },
-  SynchronizedLinks,
+  SynchronizedLinks {
+    sessions:     Vec<NasupSession>,
+    agenda_items: Vec<NasupAgendaItem>,
+  },
+  Completed,
 }
 
 impl MasterState {
-  pub fn completed(&self) -> bool { matches!(self, Self::SynchronizedLinks) }
+  pub fn completed(&self) -> bool { matches!(self, Self::Completed) }
 
   pub async fn step(self, config: &Config) -> miette::Result<Self> {
     let old_state_step = self.kind();
     let new_state: MasterState = match self {
-      MasterState::Start => MasterState::FetchedSheets {
-        sessions_worksheet:  fetch_xlsx_from_google_sheets(
-          &config.spreadsheet_id_sessions,
-        )
-        .await?
-        .get_worksheet("2026 Detailed Schedule")
-        .context("failed to get correct worksheet from sessions sheet")?,
-        presenter_worksheet: fetch_xlsx_from_google_sheets(
-          &config.spreadsheet_id_presenter_institutions,
-        )
-        .await?
-        .get_worksheet("oa_export.xlsx")
-        .context(
-          "failed to get correct worksheet from presenter institutions sheet",
-        )?,
-        strands_worksheet:   fetch_xlsx_from_google_sheets(
-          &config.spreadsheet_id_strands,
-        )
-        .await?
-        .get_worksheet("oa_export.xlsx")
-        .context("failed to get correct worksheet from strands spreadsheet")?,
-      },
+      MasterState::Start => {
+        let mut strands_spreadsheet =
+          fetch_xlsx_from_google_sheets(&config.spreadsheet_id_strands).await?;
+        let agenda_worksheet = match &config.settings.agenda {
+          Some(agenda_settings) => Some(
+            fetch_xlsx_from_google_sheets(&agenda_settings.spreadsheet_id)
+              .await?
+              .get_worksheet(&agenda_settings.worksheet)
+              .context("failed to get agenda worksheet from agenda sheet")?,
+          ),
+          None => None,
+        };
+        MasterState::FetchedSheets {
+          sessions_worksheet: fetch_xlsx_from_google_sheets(
+            &config.spreadsheet_id_sessions,
+          )
+          .await?
+          .get_worksheet("2026 Detailed Schedule")
+          .context("failed to get correct worksheet from sessions sheet")?,
+          presenter_worksheet: fetch_xlsx_from_google_sheets(
+            &config.spreadsheet_id_presenter_institutions,
+          )
+          .await?
+          .get_worksheet("oa_export.xlsx")
+          .context(
+            "failed to get correct worksheet from presenter institutions sheet",
+          )?,
+          strands_worksheet: strands_spreadsheet
+            .get_worksheet("oa_export.xlsx")
+            .context(
+              "failed to get correct worksheet from strands spreadsheet",
+            )?,
+          strand_metadata_worksheet: strands_spreadsheet
+            .get_optional_worksheet("Strand Metadata")
+            .context(
+              "failed to get strand metadata worksheet from strands \
+               spreadsheet",
+            )?,
+          agenda_worksheet,
+        }
+      }
 
       MasterState::FetchedSheets {
         sessions_worksheet,
         presenter_worksheet,
         strands_worksheet,
+        strand_metadata_worksheet,
+        agenda_worksheet,
       } => MasterState::ParsedInputs {
-        sessions:   parse_nasup_sessions_from_worksheet(sessions_worksheet)
-          .context("failed to parse nasup session data from spreadsheet")?,
-        presenters: parse_nasup_presenter_institutions_from_worksheet(
+        sessions:        parse_nasup_sessions_from_worksheet(
+          sessions_worksheet,
+          &config.settings,
+        )
+        .context("failed to parse nasup session data from spreadsheet")?,
+        presenters:      parse_nasup_presenter_institutions_from_worksheet(
           presenter_worksheet,
         )
         .context(
           "failed to parse nasup presenter institution data from spreadsheet",
         )?,
-        strands:    parse_nasup_strands_from_worksheet(strands_worksheet)
+        strands:         parse_nasup_strands_from_worksheet(strands_worksheet)
           .context("failed to parse nasup strands data from spreadsheet")?,
+        strand_metadata: strand_metadata_worksheet
+          .map(parse_nasup_strand_metadata_from_worksheet)
+          .transpose()
+          .context("failed to parse nasup strand metadata from spreadsheet")?
+          .unwrap_or_default(),
+        agenda_items:    agenda_worksheet
+          .map(|worksheet| {
+            parse_nasup_agenda_items_from_worksheet(worksheet, &config.settings)
+          })
+          .transpose()
+          .context("failed to parse nasup agenda items from spreadsheet")?
+          .unwrap_or_default(),
       },
 
       MasterState::ParsedInputs {
         sessions,
         presenters,
         strands,
+        strand_metadata,
+        agenda_items,
       } => MasterState::SynthesizedInputs {
-        sessions: synthesize_parsed_nasup_data(sessions, presenters, strands)
-          .context("failed to synthesize nasup data")?,
+        sessions:     synthesize_parsed_nasup_data(
+          sessions,
+          presenters,
+          strands,
+          strand_metadata,
+        )
+        .context("failed to synthesize nasup data")?,
+        agenda_items: synthesize_parsed_nasup_agenda_items(agenda_items),
       },
 
-      MasterState::SynthesizedInputs { sessions } => {
-        MasterState::FetchedStrandsState {
-          sessions:         sessions.clone(),
-          intended_strands: nasup_sessions_to_guidebook_schedule_tracks(
-            config,
-            sessions.as_slice(),
-          )?,
-          existing_strands: fetch_all_guidebook_entities(
-            config,
-            "/schedule-tracks",
-          )
-          .await?,
-        }
-      }
+      MasterState::SynthesizedInputs {
+        sessions,
+        agenda_items,
+      } => MasterState::FetchedStrandsState {
+        sessions:         sessions.clone(),
+        agenda_items:     agenda_items.clone(),
+        intended_strands: nasup_sessions_to_guidebook_schedule_tracks(
+          config,
+          sessions.as_slice(),
+          agenda_items.as_slice(),
+        )?,
+        existing_strands: fetch_all_guidebook_entities(
+          config,
+          "/schedule-tracks",
+        )
+        .await?,
+      },
 
       MasterState::FetchedStrandsState {
         sessions,
+        agenda_items,
         intended_strands,
         existing_strands,
       } => MasterState::CalculatedStrandsReconciliation {
         sessions,
+        agenda_items,
         strands_reconciliation:
           reconcile_intended_and_existing_guidebook_schedule_tracks(
             &intended_strands,
             &existing_strands,
+            &config
+              .store
+              .sync_bases(SyncedEntityKind::ScheduleTrack)
+              .context("failed to read schedule track sync bases from store")?,
           )
           .context(
             "failed to reconcile intended and existing guidebook session \
@@ -217,6 +344,7 @@ impl MasterState {
 
       MasterState::CalculatedStrandsReconciliation {
         sessions,
+        agenda_items,
         strands_reconciliation,
       } => {
         strands_reconciliation
@@ -229,6 +357,7 @@ impl MasterState {
 
         MasterState::ExecutedStrandsReconciliation {
           sessions,
+          agenda_items,
           existing_strands: fetch_all_guidebook_entities(
             config,
             "/schedule-tracks",
@@ -239,13 +368,16 @@ impl MasterState {
 
       MasterState::ExecutedStrandsReconciliation {
         sessions,
+        agenda_items,
         existing_strands,
       } => MasterState::FetchedLocationState {
         sessions: sessions.clone(),
+        agenda_items: agenda_items.clone(),
         existing_strands,
         intended_locations: nasup_sessions_to_guidebook_locations(
           config,
           sessions.as_slice(),
+          agenda_items.as_slice(),
         )?,
         existing_locations: fetch_all_guidebook_entities(config, "/locations")
           .await?,
@@ -253,25 +385,32 @@ impl MasterState {
 
       MasterState::FetchedLocationState {
         sessions,
+        agenda_items,
         existing_strands,
         intended_locations,
         existing_locations,
       } => MasterState::CalculatedLocationReconciliation {
         sessions,
+        agenda_items,
         existing_strands,
         locations_reconciliation:
           reconcile_intended_and_existing_guidebook_locations(
+            &config.settings,
             &intended_locations,
             &existing_locations,
+            &config
+              .store
+              .sync_bases(SyncedEntityKind::Location)
+              .context("failed to read location sync bases from store")?,
           )
           .context(
-            "failed to reconcile intended and existing guidebook session \
-             tracks",
+            "failed to reconcile intended and existing guidebook locations",
           )?,
       },
 
       MasterState::CalculatedLocationReconciliation {
         sessions,
+        agenda_items,
         existing_strands,
         locations_reconciliation,
       } => {
@@ -285,6 +424,7 @@ impl MasterState {
 
         MasterState::ExecutedLocationReconciliation {
           sessions,
+          agenda_items,
           existing_strands,
           existing_locations: fetch_all_guidebook_entities(
             config,
@@ -296,10 +436,12 @@ impl MasterState {
 
       MasterState::ExecutedLocationReconciliation {
         sessions,
+        agenda_items,
         existing_strands,
         existing_locations,
       } => MasterState::FetchedGuidebookPresenterState {
         sessions: sessions.clone(),
+        agenda_items,
         existing_strands,
         existing_locations,
         intended_presenters: nasup_sessions_to_guidebook_presenters(
@@ -310,7 +452,7 @@ impl MasterState {
           config,
           &format!(
             "/custom-list-items/?custom_lists={list_id}",
-            list_id = config.presenter_custom_list_id
+            list_id = config.presenter_list_id()?
           ),
         )
         .await?,
@@ -318,18 +460,24 @@ impl MasterState {
 
       MasterState::FetchedGuidebookPresenterState {
         sessions,
+        agenda_items,
         existing_strands,
         existing_locations,
         intended_presenters,
         existing_presenters,
       } => MasterState::CalculatedPresenterReconciliation {
         sessions,
+        agenda_items,
         existing_strands,
         existing_locations,
         presenter_reconciliation:
           reconcile_intended_and_existing_guidebook_presenters(
             &intended_presenters,
             &existing_presenters,
+            &config
+              .store
+              .sync_bases(SyncedEntityKind::Presenter)
+              .context("failed to read presenter sync bases from store")?,
           )
           .context(
             "failed to reconcile intended and existing guidebook presenters",
@@ -338,6 +486,7 @@ impl MasterState {
 
       MasterState::CalculatedPresenterReconciliation {
         sessions,
+        agenda_items,
         existing_strands,
         existing_locations,
         presenter_reconciliation,
@@ -348,77 +497,224 @@ impl MasterState {
           .context(
             "failed to reconcile intended and existing guidebook presenters",
           )?;
+        let existing_presenters = fetch_all_guidebook_entities(
+          config,
+          &format!(
+            "/custom-list-items/?custom_lists={list_id}",
+            list_id = config.presenter_list_id()?
+          ),
+        )
+        .await?;
+        synchronize_presenter_headshots(
+          config,
+          &nasup_sessions_to_presenter_headshots(config, &sessions)
+            .context("failed to extract presenter headshots")?,
+          &existing_presenters,
+        )
+        .await
+        .context("failed to synchronize presenter headshots")?;
         MasterState::ExecutedPresenterReconciliation {
           sessions,
+          agenda_items,
           existing_strands,
           existing_locations,
-          existing_presenters: fetch_all_guidebook_entities(
+          existing_presenters,
+        }
+      }
+
+      MasterState::ExecutedPresenterReconciliation {
+        sessions,
+        agenda_items,
+        existing_strands,
+        existing_locations,
+        existing_presenters,
+      } => {
+        let intended_custom_lists =
+          fetch_intended_custom_lists(config, &sessions)
+            .await
+            .context("failed to fetch intended custom lists")?;
+        MasterState::FetchedCustomListState {
+          sessions,
+          agenda_items,
+          existing_strands,
+          existing_locations,
+          existing_presenters,
+          existing_custom_list_items: fetch_existing_custom_list_items(
             config,
-            &format!(
-              "/custom-list-items/?custom_lists={list_id}",
-              list_id = config.presenter_custom_list_id
-            ),
+            &intended_custom_lists,
           )
           .await?,
+          intended_custom_lists,
         }
       }
 
-      MasterState::ExecutedPresenterReconciliation {
+      MasterState::FetchedCustomListState {
         sessions,
+        agenda_items,
         existing_strands,
         existing_locations,
         existing_presenters,
+        intended_custom_lists,
+        existing_custom_list_items,
       } => {
-        let intended_sessions = sessions
-          .into_iter()
-          .map(|ns| {
-            nasup_session_to_guidebook_session(
-              config,
-              ns,
-              &existing_strands,
-              &existing_locations,
-              &existing_presenters,
+        let sync_bases = config
+          .store
+          .sync_bases(SyncedEntityKind::CustomListItem)
+          .context("failed to read custom list item sync bases from store")?;
+        let custom_list_reconciliations = intended_custom_lists
+          .iter()
+          .map(|list| {
+            reconcile_intended_and_existing_guidebook_custom_list_items(
+              list,
+              existing_custom_list_items
+                .get(&list.list_id)
+                .map_or(&[], Vec::as_slice),
+              &sync_bases,
             )
-            .context("failed to convert nasup session to guidebook session")
           })
-          .try_collect::<Vec<_>>()?;
-        let mut import_id_to_links_map = HashMap::new();
-        let intended_sessions = intended_sessions
-          .into_iter()
-          .map(|WithLinks(s, links)| {
-            import_id_to_links_map.insert(s.import_id.clone().unwrap(), links);
-            s
-          })
-          .collect();
+          .collect::<miette::Result<_>>()
+          .context(
+            "failed to reconcile intended and existing guidebook custom list \
+             items",
+          )?;
+        MasterState::CalculatedCustomListReconciliation {
+          sessions,
+          agenda_items,
+          existing_strands,
+          existing_locations,
+          existing_presenters,
+          intended_custom_lists,
+          custom_list_reconciliations,
+        }
+      }
+
+      MasterState::CalculatedCustomListReconciliation {
+        sessions,
+        agenda_items,
+        existing_strands,
+        existing_locations,
+        existing_presenters,
+        intended_custom_lists,
+        custom_list_reconciliations,
+      } => {
+        for reconciliation in &custom_list_reconciliations {
+          reconciliation
+            .execute_reconciliation(config)
+            .await
+            .context(
+              "failed to reconcile intended and existing guidebook custom \
+               list items",
+            )?;
+        }
+        let existing_custom_list_items =
+          fetch_existing_custom_list_items(config, &intended_custom_lists)
+            .await?;
+        if let Some(institution_links_plan) = plan_institution_links(
+          config,
+          &sessions,
+          &existing_presenters,
+          &existing_custom_list_items,
+        )
+        .await?
+        {
+          institution_links_plan
+            .execute(config)
+            .await
+            .context("failed to synchronize institution links")?;
+        }
+        MasterState::ExecutedCustomListReconciliation {
+          sessions,
+          agenda_items,
+          existing_strands,
+          existing_locations,
+          existing_presenters,
+          intended_custom_lists,
+          existing_custom_list_items,
+        }
+      }
+
+      MasterState::ExecutedCustomListReconciliation {
+        sessions,
+        agenda_items,
+        existing_strands,
+        existing_locations,
+        existing_presenters,
+        intended_custom_lists,
+        existing_custom_list_items,
+      } => {
+        let (mut intended_sessions, mut import_id_to_links_map) =
+          nasup_sessions_to_guidebook_sessions(
+            config,
+            sessions.clone(),
+            &existing_strands,
+            &existing_locations,
+            &existing_presenters,
+          )?;
+        // agenda items have no links of their own, so they stay out of the
+        // link map and whatever the builder links to them is left alone
+        intended_sessions.extend(nasup_agenda_items_to_guidebook_sessions(
+          config,
+          &agenda_items,
+          &existing_strands,
+          &existing_locations,
+        )?);
+        let custom_list_item_ids = link_custom_list_items_to_sessions(
+          &intended_custom_lists,
+          &existing_custom_list_items,
+          &sessions,
+          &mut import_id_to_links_map,
+        );
 
         MasterState::FetchedGuidebookSessionState {
+          sessions,
+          agenda_items,
+          link_scope: LinkScope {
+            custom_list_item_ids: existing_presenters
+              .iter()
+              .filter_map(|p| p.id)
+              .chain(custom_list_item_ids)
+              .collect::<HashSet<_>>(),
+            ..Default::default()
+          },
           intended_sessions,
           existing_sessions: fetch_all_guidebook_entities(config, "/sessions")
             .await?,
-          intended_session_import_id_to_presenter_link_map:
-            import_id_to_links_map,
+          intended_session_links_by_import_id: import_id_to_links_map,
         }
       }
 
       MasterState::FetchedGuidebookSessionState {
+        sessions,
+        agenda_items,
+        link_scope,
         intended_sessions,
         existing_sessions,
-        intended_session_import_id_to_presenter_link_map,
+        intended_session_links_by_import_id,
       } => MasterState::CalculatedSessionReconciliation {
+        sessions,
+        agenda_items,
+        link_scope,
         session_reconciliation:
           reconcile_intended_and_existing_guidebook_sessions(
             &intended_sessions,
             &existing_sessions,
+            &config
+              .store
+              .sync_bases(SyncedEntityKind::Session)
+              .context("failed to read session sync bases from store")?,
           )
           .context(
             "failed to reconcile intended and existing guidebook sessions",
           )?,
-        intended_session_import_id_to_presenter_link_map,
+        intended_session_links_by_import_id,
       },
 
       MasterState::CalculatedSessionReconciliation {
+        sessions,
+        agenda_items,
+        link_scope,
         session_reconciliation,
-        intended_session_import_id_to_presenter_link_map,
+        intended_session_links_by_import_id,
       } => {
         session_reconciliation
           .execute_reconciliation(config)
@@ -430,34 +726,49 @@ impl MasterState {
         let new_session_state =
           fetch_all_guidebook_entities::<GuidebookSession>(config, "/sessions")
             .await?;
-        let intended_session_to_presenter_link_map =
-          intended_session_import_id_to_presenter_link_map
-            .into_iter()
-            .filter_map(|(iid, links)| {
-              new_session_state
-                .iter()
-                .find(|s| s.import_id.as_ref().unwrap() == &iid)
-                .map(|s| (s.id.unwrap(), links))
-            })
-            .collect::<HashMap<_, _>>();
+        let intended_session_links = resolve_intended_session_links(
+          intended_session_links_by_import_id,
+          &new_session_state,
+        );
         MasterState::ExecutedSessionReconciliation {
-          intended_session_to_presenter_link_map,
+          sessions,
+          agenda_items,
+          link_scope: LinkScope {
+            session_ids: intended_session_links.keys().copied().collect(),
+            ..link_scope
+          },
+          intended_session_links,
         }
       }
 
       MasterState::ExecutedSessionReconciliation {
-        intended_session_to_presenter_link_map,
+        sessions,
+        agenda_items,
+        link_scope,
+        intended_session_links,
       } => {
-        synchronize_session_links(
-          config,
-          intended_session_to_presenter_link_map,
-        )
-        .await
-        .context("failed to synchronize links")?;
-        MasterState::SynchronizedLinks
+        synchronize_session_links(config, intended_session_links, &link_scope)
+          .await
+          .context("failed to synchronize links")?;
+        MasterState::SynchronizedLinks {
+          sessions,
+          agenda_items,
+        }
+      }
+
+      MasterState::SynchronizedLinks {
+        sessions,
+        agenda_items,
+      } => {
+        if config.verify_after_sync {
+          verify_convergence(config, sessions, agenda_items)
+            .await
+            .context("sync did not converge")?;
+        }
+        MasterState::Completed
       }
 
-      MasterState::SynchronizedLinks => unreachable!(),
+      MasterState::Completed => unreachable!(),
     };
 
     info!(
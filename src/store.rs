@@ -0,0 +1,572 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fmt,
+  path::Path,
+  str::FromStr,
+  sync::{Mutex, OnceLock},
+};
+
+use miette::{Context, IntoDiagnostic, bail, miette};
+use rusqlite::{Connection, OptionalExtension};
+use tracing::{debug, instrument};
+
+use crate::guidebook::model::SyncBase;
+
+const SCHEMA: &str = "
+  CREATE TABLE IF NOT EXISTS runs (
+    id         INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at TEXT NOT NULL,
+    command    TEXT NOT NULL
+  );
+  CREATE TABLE IF NOT EXISTS mutations (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id      INTEGER NOT NULL REFERENCES runs (id),
+    recorded_at TEXT NOT NULL,
+    kind        TEXT NOT NULL,
+    endpoint    TEXT NOT NULL,
+    entity_id   INTEGER NOT NULL,
+    request     TEXT,
+    before      TEXT,
+    after       TEXT
+  );
+  CREATE TABLE IF NOT EXISTS pending_mutations (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_id      INTEGER NOT NULL REFERENCES runs (id),
+    recorded_at TEXT NOT NULL,
+    kind        TEXT NOT NULL,
+    endpoint    TEXT NOT NULL,
+    entity_id   INTEGER,
+    request     TEXT,
+    before      TEXT
+  );
+  CREATE TABLE IF NOT EXISTS sync_bases (
+    kind       TEXT NOT NULL,
+    entity_id  INTEGER NOT NULL,
+    fields     TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (kind, entity_id)
+  );
+  CREATE TABLE IF NOT EXISTS headshots (
+    entity_id   INTEGER PRIMARY KEY,
+    source      TEXT NOT NULL,
+    uploaded_at TEXT NOT NULL
+  );
+  CREATE TABLE IF NOT EXISTS guide_custom_lists (
+    guide_id    INTEGER NOT NULL,
+    key         TEXT NOT NULL,
+    list_id     INTEGER NOT NULL,
+    recorded_at TEXT NOT NULL,
+    PRIMARY KEY (guide_id, key)
+  );
+";
+
+/// The local SQLite database that journals every mutation made against
+/// Guidebook and remembers the last-synced values of managed fields, the
+/// sources of uploaded headshots and the IDs of provisioned custom lists.
+#[derive(Debug)]
+pub struct Store {
+  conn:   Mutex<Connection>,
+  run_id: OnceLock<i64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MutationKind {
+  Create,
+  Update,
+  Delete,
+}
+
+impl fmt::Display for MutationKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      MutationKind::Create => f.write_str("create"),
+      MutationKind::Update => f.write_str("update"),
+      MutationKind::Delete => f.write_str("delete"),
+    }
+  }
+}
+
+impl FromStr for MutationKind {
+  type Err = miette::Report;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Ok(match s {
+      "create" => MutationKind::Create,
+      "update" => MutationKind::Update,
+      "delete" => MutationKind::Delete,
+      k => bail!("found unknown mutation kind in journal: {k:?}"),
+    })
+  }
+}
+
+/// The kinds of entities whose managed fields are three-way merged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncedEntityKind {
+  Session,
+  Presenter,
+  ScheduleTrack,
+  Location,
+  CustomListItem,
+}
+
+impl SyncedEntityKind {
+  fn as_str(&self) -> &'static str {
+    match self {
+      SyncedEntityKind::Session => "session",
+      SyncedEntityKind::Presenter => "presenter",
+      SyncedEntityKind::ScheduleTrack => "schedule_track",
+      SyncedEntityKind::Location => "location",
+      SyncedEntityKind::CustomListItem => "custom_list_item",
+    }
+  }
+}
+
+/// A single journaled mutation of a Guidebook entity.
+#[derive(Clone, Debug)]
+pub struct Mutation {
+  pub kind:      MutationKind,
+  /// The API endpoint the entity lives under, e.g. `/sessions`.
+  pub endpoint:  String,
+  pub entity_id: u32,
+  /// The payload that was sent to Guidebook, if any.
+  pub request:   Option<serde_json::Value>,
+  /// The state of the entity before the mutation. Empty for creations.
+  pub before:    Option<serde_json::Value>,
+  /// The state of the entity after the mutation. Empty for deletions.
+  pub after:     Option<serde_json::Value>,
+}
+
+/// A mutation journaled before its request was sent, which stays pending
+/// until the response is journaled too. One left pending may or may not have
+/// been applied.
+#[derive(Clone, Debug)]
+pub struct PendingMutation {
+  pub kind:      MutationKind,
+  pub endpoint:  String,
+  /// Empty for creations, since the ID is only known from the response.
+  pub entity_id: Option<u32>,
+  pub request:   Option<serde_json::Value>,
+  pub before:    Option<serde_json::Value>,
+}
+
+impl Store {
+  pub fn open(path: &Path) -> miette::Result<Self> {
+    let conn = Connection::open(path)
+      .into_diagnostic()
+      .context(format!("failed to open store at {path:?}"))?;
+    conn
+      .execute_batch(SCHEMA)
+      .into_diagnostic()
+      .context("failed to apply store schema")?;
+
+    Ok(Self {
+      conn:   Mutex::new(conn),
+      run_id: OnceLock::new(),
+    })
+  }
+
+  /// Starts a new run, which all following mutations are journaled under.
+  #[instrument(skip(self))]
+  pub fn begin_run(&self, command: &str) -> miette::Result<i64> {
+    let conn = self.conn.lock().unwrap();
+    conn
+      .execute(
+        "INSERT INTO runs (started_at, command) VALUES (?1, ?2)",
+        (chrono::Utc::now().to_rfc3339(), command),
+      )
+      .into_diagnostic()
+      .context("failed to insert run into store")?;
+    let run_id = conn.last_insert_rowid();
+    self
+      .run_id
+      .set(run_id)
+      .map_err(|_| miette!("a run has already been started"))?;
+    debug!(run_id, "began run");
+
+    Ok(run_id)
+  }
+
+  fn current_run_id(&self) -> miette::Result<i64> {
+    self
+      .run_id
+      .get()
+      .copied()
+      .ok_or(miette!("tried to record a mutation outside of a run"))
+  }
+
+  /// Journals a mutation that is about to be sent, returning the ID to
+  /// complete or discard it with.
+  pub fn begin_mutation(
+    &self,
+    mutation: &PendingMutation,
+  ) -> miette::Result<i64> {
+    let run_id = self.current_run_id()?;
+    let conn = self.conn.lock().unwrap();
+    conn
+      .execute(
+        "INSERT INTO pending_mutations (run_id, recorded_at, kind, endpoint, \
+         entity_id, request, before) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        (
+          run_id,
+          chrono::Utc::now().to_rfc3339(),
+          mutation.kind.to_string(),
+          &mutation.endpoint,
+          mutation.entity_id,
+          to_text(&mutation.request),
+          to_text(&mutation.before),
+        ),
+      )
+      .into_diagnostic()
+      .context("failed to insert pending mutation into store")?;
+
+    Ok(conn.last_insert_rowid())
+  }
+
+  /// Journals a mutation that Guidebook applied, replacing its pending entry.
+  pub fn complete_mutation(
+    &self,
+    pending_id: i64,
+    mutation: &Mutation,
+  ) -> miette::Result<()> {
+    let run_id = self.current_run_id()?;
+    let mut conn = self.conn.lock().unwrap();
+    let tx = conn
+      .transaction()
+      .into_diagnostic()
+      .context("failed to begin store transaction")?;
+    tx.execute(
+      "INSERT INTO mutations (run_id, recorded_at, kind, endpoint, entity_id, \
+       request, before, after) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+      (
+        run_id,
+        chrono::Utc::now().to_rfc3339(),
+        mutation.kind.to_string(),
+        &mutation.endpoint,
+        mutation.entity_id,
+        to_text(&mutation.request),
+        to_text(&mutation.before),
+        to_text(&mutation.after),
+      ),
+    )
+    .into_diagnostic()
+    .context("failed to insert mutation into store")?;
+    tx.execute("DELETE FROM pending_mutations WHERE id = ?1", [pending_id])
+      .into_diagnostic()
+      .context("failed to delete pending mutation from store")?;
+    tx.commit()
+      .into_diagnostic()
+      .context("failed to commit mutation to store")?;
+
+    Ok(())
+  }
+
+  /// Drops the pending entry of a mutation that Guidebook rejected.
+  pub fn discard_mutation(&self, pending_id: i64) -> miette::Result<()> {
+    self
+      .conn
+      .lock()
+      .unwrap()
+      .execute("DELETE FROM pending_mutations WHERE id = ?1", [pending_id])
+      .into_diagnostic()
+      .context("failed to delete pending mutation from store")?;
+
+    Ok(())
+  }
+
+  /// Returns the mutations of a run in the order they were made.
+  pub fn run_mutations(&self, run_id: i64) -> miette::Result<Vec<Mutation>> {
+    let conn = self.conn.lock().unwrap();
+
+    let run_exists = conn
+      .query_row("SELECT id FROM runs WHERE id = ?1", [run_id], |_| Ok(()))
+      .optional()
+      .into_diagnostic()
+      .context("failed to query store for run")?
+      .is_some();
+    if !run_exists {
+      bail!("found no run with ID {run_id} in store");
+    }
+
+    let mut stmt = conn
+      .prepare(
+        "SELECT kind, endpoint, entity_id, request, before, after FROM \
+         mutations WHERE run_id = ?1 ORDER BY id",
+      )
+      .into_diagnostic()
+      .context("failed to prepare mutation query")?;
+    let rows = stmt
+      .query_map([run_id], |row| {
+        Ok((
+          row.get::<_, String>(0)?,
+          row.get::<_, String>(1)?,
+          row.get::<_, u32>(2)?,
+          row.get::<_, Option<String>>(3)?,
+          row.get::<_, Option<String>>(4)?,
+          row.get::<_, Option<String>>(5)?,
+        ))
+      })
+      .into_diagnostic()
+      .context("failed to query store for mutations")?;
+
+    rows
+      .map(|row| {
+        let (kind, endpoint, entity_id, request, before, after) =
+          row.into_diagnostic()?;
+        Ok(Mutation {
+          kind: kind.parse()?,
+          endpoint,
+          entity_id,
+          request: from_text(request)?,
+          before: from_text(before)?,
+          after: from_text(after)?,
+        })
+      })
+      .try_collect()
+  }
+
+  /// Returns the mutations of a run that were never completed, in the order
+  /// they were begun.
+  pub fn run_pending_mutations(
+    &self,
+    run_id: i64,
+  ) -> miette::Result<Vec<PendingMutation>> {
+    let conn = self.conn.lock().unwrap();
+    let mut stmt = conn
+      .prepare(
+        "SELECT kind, endpoint, entity_id, request, before FROM \
+         pending_mutations WHERE run_id = ?1 ORDER BY id",
+      )
+      .into_diagnostic()
+      .context("failed to prepare pending mutation query")?;
+    let rows = stmt
+      .query_map([run_id], |row| {
+        Ok((
+          row.get::<_, String>(0)?,
+          row.get::<_, String>(1)?,
+          row.get::<_, Option<u32>>(2)?,
+          row.get::<_, Option<String>>(3)?,
+          row.get::<_, Option<String>>(4)?,
+        ))
+      })
+      .into_diagnostic()
+      .context("failed to query store for pending mutations")?;
+
+    rows
+      .map(|row| {
+        let (kind, endpoint, entity_id, request, before) =
+          row.into_diagnostic()?;
+        Ok(PendingMutation {
+          kind: kind.parse()?,
+          endpoint,
+          entity_id,
+          request: from_text(request)?,
+          before: from_text(before)?,
+        })
+      })
+      .try_collect()
+  }
+
+  /// Returns the last-synced values of every entity of the given kind, keyed
+  /// by Guidebook ID.
+  pub fn sync_bases(
+    &self,
+    kind: SyncedEntityKind,
+  ) -> miette::Result<HashMap<u32, SyncBase>> {
+    let conn = self.conn.lock().unwrap();
+    let mut stmt = conn
+      .prepare("SELECT entity_id, fields FROM sync_bases WHERE kind = ?1")
+      .into_diagnostic()
+      .context("failed to prepare sync base query")?;
+    let rows = stmt
+      .query_map([kind.as_str()], |row| {
+        Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?))
+      })
+      .into_diagnostic()
+      .context("failed to query store for sync bases")?;
+
+    rows
+      .map(|row| {
+        let (entity_id, fields) = row.into_diagnostic()?;
+        let fields = serde_json::from_str(&fields)
+          .into_diagnostic()
+          .context("failed to parse sync base as JSON")?;
+        Ok((entity_id, fields))
+      })
+      .try_collect()
+  }
+
+  pub fn record_sync_base(
+    &self,
+    kind: SyncedEntityKind,
+    entity_id: u32,
+    base: &SyncBase,
+  ) -> miette::Result<()> {
+    let fields = serde_json::to_string(base)
+      .into_diagnostic()
+      .context("failed to serialize sync base")?;
+    self
+      .conn
+      .lock()
+      .unwrap()
+      .execute(
+        "INSERT INTO sync_bases (kind, entity_id, fields, updated_at) VALUES \
+         (?1, ?2, ?3, ?4) ON CONFLICT (kind, entity_id) DO UPDATE SET fields \
+         = excluded.fields, updated_at = excluded.updated_at",
+        (
+          kind.as_str(),
+          entity_id,
+          fields,
+          chrono::Utc::now().to_rfc3339(),
+        ),
+      )
+      .into_diagnostic()
+      .context("failed to upsert sync base into store")?;
+
+    Ok(())
+  }
+
+  /// Returns the source every presenter's headshot was last uploaded from,
+  /// keyed by Guidebook ID.
+  pub fn headshot_sources(&self) -> miette::Result<HashMap<u32, String>> {
+    let conn = self.conn.lock().unwrap();
+    let mut stmt = conn
+      .prepare("SELECT entity_id, source FROM headshots")
+      .into_diagnostic()
+      .context("failed to prepare headshot query")?;
+    let rows = stmt
+      .query_map([], |row| {
+        Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?))
+      })
+      .into_diagnostic()
+      .context("failed to query store for headshots")?;
+
+    rows
+      .map(|row| row.into_diagnostic())
+      .try_collect()
+      .context("failed to read headshot from store")
+  }
+
+  pub fn record_headshot_source(
+    &self,
+    entity_id: u32,
+    source: &str,
+  ) -> miette::Result<()> {
+    self
+      .conn
+      .lock()
+      .unwrap()
+      .execute(
+        "INSERT INTO headshots (entity_id, source, uploaded_at) VALUES (?1, \
+         ?2, ?3) ON CONFLICT (entity_id) DO UPDATE SET source = \
+         excluded.source, uploaded_at = excluded.uploaded_at",
+        (entity_id, source, chrono::Utc::now().to_rfc3339()),
+      )
+      .into_diagnostic()
+      .context("failed to upsert headshot into store")?;
+
+    Ok(())
+  }
+
+  /// The IDs of every entity created under an endpoint, like `/links`, as
+  /// journaled.
+  pub fn created_entity_ids(
+    &self,
+    endpoint: &str,
+  ) -> miette::Result<HashSet<u32>> {
+    let conn = self.conn.lock().unwrap();
+    let mut stmt = conn
+      .prepare(
+        "SELECT entity_id FROM mutations WHERE kind = ?1 AND endpoint = ?2",
+      )
+      .into_diagnostic()
+      .context("failed to prepare created entity query")?;
+    let rows = stmt
+      .query_map((MutationKind::Create.to_string(), endpoint), |row| {
+        row.get::<_, u32>(0)
+      })
+      .into_diagnostic()
+      .context("failed to query store for created entities")?;
+
+    rows
+      .map(|row| row.into_diagnostic())
+      .try_collect()
+      .context("failed to read created entity from store")
+  }
+
+  /// The ID of the custom list provisioned in a guide for a list key, if any.
+  pub fn custom_list_id(
+    &self,
+    guide_id: u32,
+    key: &str,
+  ) -> miette::Result<Option<u32>> {
+    self
+      .conn
+      .lock()
+      .unwrap()
+      .query_row(
+        "SELECT list_id FROM guide_custom_lists WHERE guide_id = ?1 AND key = \
+         ?2",
+        (guide_id, key),
+        |row| row.get::<_, u32>(0),
+      )
+      .optional()
+      .into_diagnostic()
+      .context("failed to query store for custom list")
+  }
+
+  pub fn record_custom_list_id(
+    &self,
+    guide_id: u32,
+    key: &str,
+    list_id: u32,
+  ) -> miette::Result<()> {
+    self
+      .conn
+      .lock()
+      .unwrap()
+      .execute(
+        "INSERT INTO guide_custom_lists (guide_id, key, list_id, recorded_at) \
+         VALUES (?1, ?2, ?3, ?4) ON CONFLICT (guide_id, key) DO UPDATE SET \
+         list_id = excluded.list_id, recorded_at = excluded.recorded_at",
+        (guide_id, key, list_id, chrono::Utc::now().to_rfc3339()),
+      )
+      .into_diagnostic()
+      .context("failed to upsert custom list into store")?;
+
+    Ok(())
+  }
+}
+
+fn to_text(value: &Option<serde_json::Value>) -> Option<String> {
+  value.as_ref().map(|v| v.to_string())
+}
+
+fn from_text(
+  text: Option<String>,
+) -> miette::Result<Option<serde_json::Value>> {
+  text
+    .map(|v| serde_json::from_str::<serde_json::Value>(&v))
+    .transpose()
+    .into_diagnostic()
+    .context("failed to parse journaled payload as JSON")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn scopes_custom_list_ids_by_guide() {
+    let store = Store::open(Path::new(":memory:")).unwrap();
+    store.record_custom_list_id(1, "presenters", 10).unwrap();
+    store.record_custom_list_id(2, "presenters", 20).unwrap();
+
+    assert_eq!(store.custom_list_id(1, "presenters").unwrap(), Some(10));
+    assert_eq!(store.custom_list_id(2, "presenters").unwrap(), Some(20));
+    assert_eq!(store.custom_list_id(3, "presenters").unwrap(), None);
+
+    store.record_custom_list_id(1, "presenters", 11).unwrap();
+    assert_eq!(store.custom_list_id(1, "presenters").unwrap(), Some(11));
+    assert_eq!(store.custom_list_id(2, "presenters").unwrap(), Some(20));
+  }
+}
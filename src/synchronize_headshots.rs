@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+
+use miette::{Context, IntoDiagnostic};
+use tracing::{debug, instrument, warn};
+
+use crate::{
+  HTTP_CLIENT,
+  config::Config,
+  guidebook::{model::GuidebookPresenter, upload_guidebook_image},
+  nasup_to_guidebook::PresenterKey,
+};
+
+/// Uploads the headshots of presenters whose headshot source changed since it
+/// was last uploaded. Guidebook only hands back a resized copy of an image,
+/// so the source is what's compared.
+#[instrument(skip_all)]
+pub async fn synchronize_presenter_headshots(
+  config: &Config,
+  headshots: &HashMap<PresenterKey, String>,
+  existing_presenters: &[GuidebookPresenter],
+) -> miette::Result<()> {
+  let uploaded = config
+    .store
+    .headshot_sources()
+    .context("failed to read headshot sources from store")?;
+
+  for presenter in existing_presenters {
+    let (Some(id), Some(name)) = (presenter.id, presenter.name.value()) else {
+      continue;
+    };
+    // as when finding presenters, a headshot without a subtitle goes to the
+    // presenter with that name whatever their subtitle
+    let subtitle = presenter.subtitle.value().cloned();
+    let Some(source) = headshots
+      .get(&(name.clone(), subtitle))
+      .or_else(|| headshots.get(&(name.clone(), None)))
+    else {
+      continue;
+    };
+    if uploaded.get(&id) == Some(source) {
+      continue;
+    }
+
+    // a missing headshot shouldn't hold up the rest of the sync
+    let (file_name, image) = match load_headshot(config, source).await {
+      Ok(headshot) => headshot,
+      Err(e) => {
+        warn!(name, source, "failed to load headshot: {e:?}");
+        continue;
+      }
+    };
+    debug!(id, name, source, "uploading presenter headshot");
+    upload_guidebook_image(
+      config,
+      "/custom-list-items/",
+      id,
+      &file_name,
+      image,
+    )
+    .await
+    .context("failed to upload presenter headshot")?;
+    config
+      .store
+      .record_headshot_source(id, source)
+      .context("failed to record source of uploaded headshot")?;
+  }
+
+  Ok(())
+}
+
+/// Reads a headshot from a URL or from the headshots directory, returning its
+/// file name and contents.
+async fn load_headshot(
+  config: &Config,
+  source: &str,
+) -> miette::Result<(String, Vec<u8>)> {
+  if source.starts_with("http://") || source.starts_with("https://") {
+    let file_name = source
+      .split(['?', '#'])
+      .next()
+      .and_then(|p| p.rsplit('/').next())
+      .filter(|n| !n.is_empty())
+      .unwrap_or("headshot.jpg")
+      .to_owned();
+    let image = HTTP_CLIENT
+      .get(source)
+      .send()
+      .await
+      .into_diagnostic()
+      .context("failed to send request to download headshot")?
+      .error_for_status()
+      .into_diagnostic()
+      .context("got server error response from headshot download")?
+      .bytes()
+      .await
+      .into_diagnostic()
+      .context("failed to read headshot download")?;
+    return Ok((file_name, image.to_vec()));
+  }
+
+  let path = config.headshots_dir.join(source);
+  let image = tokio::fs::read(&path)
+    .await
+    .into_diagnostic()
+    .context(format!("failed to read headshot at {path:?}"))?;
+  let file_name = path
+    .file_name()
+    .map_or(source.to_owned(), |n| n.to_string_lossy().into_owned());
+  Ok((file_name, image))
+}
@@ -1,142 +1,528 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use miette::Context;
-use tracing::debug;
+use futures_util::{StreamExt, TryStreamExt, stream};
+use miette::{Context, IntoDiagnostic};
+use tracing::{debug, instrument, warn};
 
 use crate::{
   config::Config,
   guidebook::{
     delete_guidebook_entity, fetch_all_guidebook_entities,
+    model::{GuidebookContentType, GuidebookLink, GuidebookSession},
     upsert_guidebook_entity,
   },
+  nasup_to_guidebook::LinksByImportId,
 };
 
+/// How many link requests are in flight at once.
+const LINK_REQUEST_CONCURRENCY: usize = 8;
+
+/// The links this tool owns. Only links between a managed session and either
+/// one of these custom list items or another managed session are ever created
+/// or deleted, so links that staff add to maps, PDFs, sponsors or other lists
+/// are left alone. Links between managed sessions are only deleted if the
+/// journal shows this tool created them.
+#[derive(Clone, Debug, Default)]
+pub struct LinkScope {
+  pub custom_list_item_ids: HashSet<u32>,
+  pub session_ids:          HashSet<u32>,
+}
+
+impl LinkScope {
+  fn owns(&self, content_type: GuidebookContentType, object_id: u32) -> bool {
+    match content_type {
+      GuidebookContentType::CustomListItem => {
+        self.custom_list_item_ids.contains(&object_id)
+      }
+      GuidebookContentType::Session => self.session_ids.contains(&object_id),
+      GuidebookContentType::Other => false,
+    }
+  }
+}
+
+/// The links a session should have, by Guidebook ID.
+#[derive(Clone, Debug, Default)]
+pub struct IntendedSessionLinks {
+  /// Presenters and other custom list items linked both to and from the
+  /// session.
+  pub custom_list_item_ids: Vec<u32>,
+  /// Other sessions linked to from the session, e.g. the other half of a
+  /// paired concurrent session. The reverse link is owned by the peer.
+  pub peer_session_ids:     Vec<u32>,
+}
+
+/// Resolves the import IDs of intended session links into Guidebook IDs,
+/// dropping sessions that don't exist in Guidebook.
+pub fn resolve_intended_session_links(
+  links_by_import_id: LinksByImportId,
+  existing_sessions: &[GuidebookSession],
+) -> HashMap<u32, IntendedSessionLinks> {
+  let session_ids_by_import_id = existing_sessions
+    .iter()
+    .filter_map(|s| Some((s.import_id.clone()?, s.id?)))
+    .collect::<HashMap<_, _>>();
+
+  links_by_import_id
+    .into_iter()
+    .filter_map(|(iid, links)| {
+      let session_id = *session_ids_by_import_id.get(&iid)?;
+      let peer_session_ids = links
+        .peer_import_ids
+        .iter()
+        .filter_map(|piid| {
+          let peer_id = session_ids_by_import_id.get(piid).copied();
+          if peer_id.is_none() {
+            warn!(import_id = piid, "could not find peer session to link to");
+          }
+          peer_id
+        })
+        .collect();
+      Some((session_id, IntendedSessionLinks {
+        custom_list_item_ids: links.custom_list_item_ids,
+        peer_session_ids,
+      }))
+    })
+    .collect()
+}
+
+#[derive(Debug)]
+pub struct LinksPlan {
+  pub links_to_delete: Vec<GuidebookLink>,
+  pub links_to_create: Vec<GuidebookLink>,
+}
+
+/// Which end of a link an object is at.
+#[derive(Clone, Copy, Debug)]
+enum LinkEnd {
+  Source,
+  Target,
+}
+
+/// Fetches the links starting from or pointing to the given objects, keyed by
+/// object ID. Links are fetched once for the content type and filtered here,
+/// so the number of requests doesn't grow with the number of objects.
+async fn fetch_links_by_object(
+  config: &Config,
+  end: LinkEnd,
+  content_type: &str,
+  object_ids: impl IntoIterator<Item = u32>,
+) -> miette::Result<HashMap<u32, Vec<GuidebookLink>>> {
+  let object_ids = object_ids.into_iter().collect::<HashSet<_>>();
+  let end_name = match end {
+    LinkEnd::Source => "source",
+    LinkEnd::Target => "target",
+  };
+  let mut links_by_object: HashMap<u32, Vec<GuidebookLink>> = HashMap::new();
+  for link in fetch_all_guidebook_entities::<GuidebookLink>(
+    config,
+    &format!("/links/?{end_name}_content_type={content_type}"),
+  )
+  .await?
+  {
+    let object_id = match end {
+      LinkEnd::Source => link.source_object_id,
+      LinkEnd::Target => link.target_object_id,
+    };
+    if object_ids.contains(&object_id) {
+      links_by_object.entry(object_id).or_default().push(link);
+    }
+  }
+  Ok(links_by_object)
+}
+
 pub async fn synchronize_session_links(
   config: &Config,
-  intended_session_to_presenter_links: HashMap<u32, Vec<u32>>,
+  intended_session_links: HashMap<u32, IntendedSessionLinks>,
+  scope: &LinkScope,
 ) -> miette::Result<()> {
-  let existing_links =
-    fetch_all_guidebook_entities::<serde_json::Value>(config, "/links").await?;
+  plan_session_links(config, intended_session_links, scope)
+    .await?
+    .execute(config)
+    .await
+}
+
+#[instrument(skip(config, intended_session_links, scope))]
+pub async fn plan_session_links(
+  config: &Config,
+  intended_session_links: HashMap<u32, IntendedSessionLinks>,
+  scope: &LinkScope,
+) -> miette::Result<LinksPlan> {
+  // only fetch links touching the managed sessions, indexed by session
+  let session_ids = intended_session_links.keys().copied().collect::<Vec<_>>();
+  let existing_outbound_links = fetch_links_by_object(
+    config,
+    LinkEnd::Source,
+    "schedule.session",
+    session_ids.iter().copied(),
+  )
+  .await?;
+  let existing_inbound_links = fetch_links_by_object(
+    config,
+    LinkEnd::Target,
+    "schedule.session",
+    session_ids,
+  )
+  .await?;
+
+  // session links made by hand are left alone, even between managed sessions
+  let created_link_ids = config
+    .store
+    .created_entity_ids("/links")
+    .context("failed to read created links from store")?;
 
+  Ok(diff_session_links(
+    config.guide_id as u32,
+    intended_session_links,
+    &existing_outbound_links,
+    &existing_inbound_links,
+    scope,
+    &created_link_ids,
+  ))
+}
+
+/// Plans the links to create and delete so that each session has exactly its
+/// intended links, out of the links it has.
+fn diff_session_links(
+  guide_id: u32,
+  intended_session_links: HashMap<u32, IntendedSessionLinks>,
+  existing_outbound_links: &HashMap<u32, Vec<GuidebookLink>>,
+  existing_inbound_links: &HashMap<u32, Vec<GuidebookLink>>,
+  scope: &LinkScope,
+  created_link_ids: &HashSet<u32>,
+) -> LinksPlan {
   let mut links_to_delete = Vec::new();
-  // from session to presenter
-  let mut outbound_links_to_create = Vec::new();
-  // from presenter to session
-  let mut inbound_links_to_create = Vec::new();
+  let mut links_to_create = Vec::new();
+  let new_link =
+    |source: (GuidebookContentType, u32),
+     target: (GuidebookContentType, u32)| GuidebookLink {
+      id: None,
+      guide_id,
+      source_object_id: source.1,
+      source_content_type: source.0,
+      target_object_id: target.1,
+      target_content_type: target.0,
+    };
+
+  for (session_id, links) in intended_session_links {
+    let session = (GuidebookContentType::Session, session_id);
 
-  for (session_id, presenter_ids) in intended_session_to_presenter_links {
     // keep track of the links we still need to make, in and out of this session
-    let mut needed_inbound_links = presenter_ids.clone();
-    let mut needed_outbound_links = presenter_ids.clone();
-
-    // for all the links out of the session
-    for existing_outbound_link in existing_links.iter().filter(|l| {
-      l["source_content_type"] == "schedule.session"
-        && l["source_object_id"] == session_id
-    }) {
-      let existing_outbound_link_target =
-        existing_outbound_link["target_object_id"].as_u64().unwrap() as u32;
-      // if the target isn't one of the given presenters, mark it for deletion
-      if !presenter_ids.contains(&existing_outbound_link_target) {
-        links_to_delete
-          .push(existing_outbound_link["id"].clone().as_u64().unwrap() as u32);
+    let mut needed_outbound_links = links
+      .custom_list_item_ids
+      .iter()
+      .map(|pid| (GuidebookContentType::CustomListItem, *pid))
+      .chain(
+        links
+          .peer_session_ids
+          .iter()
+          .map(|sid| (GuidebookContentType::Session, *sid)),
+      )
+      .collect::<Vec<_>>();
+    let mut needed_inbound_links = links
+      .custom_list_item_ids
+      .iter()
+      .map(|pid| (GuidebookContentType::CustomListItem, *pid))
+      .collect::<Vec<_>>();
+
+    // for all the owned links out of the session
+    for existing_outbound_link in existing_outbound_links
+      .get(&session_id)
+      .into_iter()
+      .flatten()
+      .filter(|l| {
+        l.source_content_type == GuidebookContentType::Session
+          && scope.owns(l.target_content_type, l.target_object_id)
+      })
+    {
+      let target = (
+        existing_outbound_link.target_content_type,
+        existing_outbound_link.target_object_id,
+      );
+      // links to other sessions are only ours if we created them
+      let created = target.0 != GuidebookContentType::Session
+        || existing_outbound_link
+          .id
+          .is_some_and(|id| created_link_ids.contains(&id));
+      // if the target isn't intended, mark it for deletion
+      if !needed_outbound_links.contains(&target) {
+        if created {
+          links_to_delete.push(existing_outbound_link.clone());
+        }
       } else {
         // otherwise mark that we don't need to create it
-        needed_outbound_links.retain(|id| *id != existing_outbound_link_target);
+        needed_outbound_links.retain(|t| *t != target);
       }
     }
 
-    // we need to create outbound links for all the presenters we didn't find
+    // we need to create outbound links for all the targets we didn't find
     // existing outbound links for
-    outbound_links_to_create.extend(
+    links_to_create.extend(
       needed_outbound_links
         .into_iter()
-        .map(|pid| (session_id, pid)),
+        .map(|target| new_link(session, target)),
     );
 
-    // for all the links into the session
-    for existing_inbound_link in existing_links.iter().filter(|l| {
-      l["target_content_type"] == "schedule.session"
-        && l["target_object_id"] == session_id
-    }) {
-      let existing_inbound_link_source =
-        existing_inbound_link["source_object_id"].as_u64().unwrap() as u32;
-      // if the source isn't one of the given presenters, mark it for deletion
-      if !presenter_ids.contains(&existing_inbound_link_source) {
-        links_to_delete
-          .push(existing_inbound_link["id"].clone().as_u64().unwrap() as u32);
+    // for all the owned links into the session from custom list items; links
+    // from other sessions are owned by those sessions' outbound links
+    for existing_inbound_link in existing_inbound_links
+      .get(&session_id)
+      .into_iter()
+      .flatten()
+      .filter(|l| {
+        l.target_content_type == GuidebookContentType::Session
+          && l.source_content_type == GuidebookContentType::CustomListItem
+          && scope.owns(l.source_content_type, l.source_object_id)
+      })
+    {
+      let source = (
+        existing_inbound_link.source_content_type,
+        existing_inbound_link.source_object_id,
+      );
+      // if the source isn't one of the given items, mark it for deletion
+      if !needed_inbound_links.contains(&source) {
+        links_to_delete.push(existing_inbound_link.clone());
       } else {
         // otherwise mark that we don't need to create it
-        needed_inbound_links.retain(|id| *id != existing_inbound_link_source);
+        needed_inbound_links.retain(|s| *s != source);
       }
     }
 
-    // we need to create inbound links for all the presenters we didn't find
+    // we need to create inbound links for all the items we didn't find
     // existing inbound links for
-    inbound_links_to_create.extend(
+    links_to_create.extend(
       needed_inbound_links
         .into_iter()
-        .map(|pid| (pid, session_id)),
+        .map(|source| new_link(source, session)),
     );
   }
 
-  // now we need to delete links
-  for link_id in links_to_delete {
-    delete_guidebook_entity(config, "/links", link_id)
-      .await
-      .context("failed to delete link during synchronization")?;
-    debug!(link_id, "deleted session link");
+  LinksPlan {
+    links_to_delete,
+    links_to_create,
   }
+}
 
-  // now to create outbound links
-  for (source_session_id, target_presenter_id) in outbound_links_to_create {
-    let payload = serde_json::json!({
-      "target_object_id": target_presenter_id,
-      "source_object_id": source_session_id,
-      "source_content_type": "schedule.session",
-      "guide": config.guide_id,
-      "target_content_type": "custom_list.customlistitem"
-    });
-    upsert_guidebook_entity(
-      config,
-      payload,
-      "/links/",
-      crate::guidebook::Modification::Create,
-    )
-    .await
-    .context(
-      "failed to create outbound link from session during synchronization",
-    )?;
-    debug!(
-      source_session_id,
-      target_presenter_id, "added outbound link from session to presenter"
+/// Plans links in both directions between custom list items, like those
+/// between institutions and their presenters. Only links between one of the
+/// intended items and one of `linked_item_ids` are owned, so the items'
+/// links to sessions and anything else are left alone.
+#[instrument(skip(config, intended_item_links, linked_item_ids))]
+pub async fn plan_custom_list_item_links(
+  config: &Config,
+  intended_item_links: HashMap<u32, Vec<u32>>,
+  linked_item_ids: &HashSet<u32>,
+) -> miette::Result<LinksPlan> {
+  let item = GuidebookContentType::CustomListItem;
+  let item_ids = intended_item_links.keys().copied().collect::<Vec<_>>();
+  let owned = |l: &GuidebookLink| {
+    l.source_content_type == item && l.target_content_type == item
+  };
+  let mut existing_outbound_links = fetch_links_by_object(
+    config,
+    LinkEnd::Source,
+    "custom_list.customlistitem",
+    item_ids.iter().copied(),
+  )
+  .await?;
+  for links in existing_outbound_links.values_mut() {
+    links.retain(|l| owned(l) && linked_item_ids.contains(&l.target_object_id));
+  }
+  let mut existing_inbound_links = fetch_links_by_object(
+    config,
+    LinkEnd::Target,
+    "custom_list.customlistitem",
+    item_ids,
+  )
+  .await?;
+  for links in existing_inbound_links.values_mut() {
+    links.retain(|l| owned(l) && linked_item_ids.contains(&l.source_object_id));
+  }
+
+  let mut links_to_delete = Vec::new();
+  let mut links_to_create = Vec::new();
+  let new_link = |source: u32, target: u32| GuidebookLink {
+    id:                  None,
+    guide_id:            config.guide_id as u32,
+    source_object_id:    source,
+    source_content_type: item,
+    target_object_id:    target,
+    target_content_type: item,
+  };
+
+  for (item_id, linked_ids) in intended_item_links {
+    let mut needed_outbound_links = linked_ids.clone();
+    for link in existing_outbound_links.get(&item_id).into_iter().flatten() {
+      if needed_outbound_links.contains(&link.target_object_id) {
+        needed_outbound_links.retain(|t| *t != link.target_object_id);
+      } else {
+        links_to_delete.push(link.clone());
+      }
+    }
+    links_to_create.extend(
+      needed_outbound_links
+        .into_iter()
+        .map(|target| new_link(item_id, target)),
+    );
+
+    let mut needed_inbound_links = linked_ids;
+    for link in existing_inbound_links.get(&item_id).into_iter().flatten() {
+      if needed_inbound_links.contains(&link.source_object_id) {
+        needed_inbound_links.retain(|s| *s != link.source_object_id);
+      } else {
+        links_to_delete.push(link.clone());
+      }
+    }
+    links_to_create.extend(
+      needed_inbound_links
+        .into_iter()
+        .map(|source| new_link(source, item_id)),
     );
   }
 
-  // now to create inbound links
-  for (source_presenter_id, target_session_id) in inbound_links_to_create {
-    let payload = serde_json::json!({
-      "target_object_id": target_session_id,
-      "source_object_id": source_presenter_id,
-      "source_content_type": "custom_list.customlistitem",
-      "guide": config.guide_id,
-      "target_content_type": "schedule.session"
-    });
-    upsert_guidebook_entity(
-      config,
-      payload,
-      "/links/",
-      crate::guidebook::Modification::Create,
+  Ok(LinksPlan {
+    links_to_delete,
+    links_to_create,
+  })
+}
+
+impl LinksPlan {
+  pub fn is_empty(&self) -> bool {
+    self.links_to_delete.is_empty() && self.links_to_create.is_empty()
+  }
+
+  #[instrument(skip(self, config))]
+  pub async fn execute(self, config: &Config) -> miette::Result<()> {
+    // now we need to delete links, journaling the state they were fetched in
+    stream::iter(self.links_to_delete)
+      .map(|link| async move {
+        let link_id = link.id.unwrap();
+        let before = serde_json::to_value(&link)
+          .into_diagnostic()
+          .context("failed to serialize link to delete")?;
+        delete_guidebook_entity(config, "/links", link_id, Some(before))
+          .await
+          .context("failed to delete link during synchronization")?;
+        debug!(link_id, "deleted link");
+        miette::Result::<()>::Ok(())
+      })
+      .buffer_unordered(LINK_REQUEST_CONCURRENCY)
+      .try_collect::<()>()
+      .await?;
+
+    // now to create links
+    stream::iter(self.links_to_create)
+      .map(|link| async move {
+        upsert_guidebook_entity(
+          config,
+          link.clone(),
+          "/links/",
+          crate::guidebook::Modification::Create,
+        )
+        .await
+        .context("failed to create link during synchronization")?;
+        debug!(
+          source_object_id = link.source_object_id,
+          source_content_type = ?link.source_content_type,
+          target_object_id = link.target_object_id,
+          target_content_type = ?link.target_content_type,
+          "added link"
+        );
+        miette::Result::<()>::Ok(())
+      })
+      .buffer_unordered(LINK_REQUEST_CONCURRENCY)
+      .try_collect::<()>()
+      .await?;
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SESSION: GuidebookContentType = GuidebookContentType::Session;
+
+  fn session_link(id: u32, source: u32, target: u32) -> GuidebookLink {
+    GuidebookLink {
+      id:                  Some(id),
+      guide_id:            1,
+      source_object_id:    source,
+      source_content_type: SESSION,
+      target_object_id:    target,
+      target_content_type: SESSION,
+    }
+  }
+
+  fn diff(
+    peers: &[(u32, Vec<u32>)],
+    existing: &[GuidebookLink],
+    created_link_ids: &[u32],
+  ) -> LinksPlan {
+    let intended = peers
+      .iter()
+      .map(|(id, peer_session_ids)| {
+        (*id, IntendedSessionLinks {
+          custom_list_item_ids: Vec::new(),
+          peer_session_ids:     peer_session_ids.clone(),
+        })
+      })
+      .collect::<HashMap<_, _>>();
+    let scope = LinkScope {
+      custom_list_item_ids: HashSet::new(),
+      session_ids:          intended.keys().copied().collect(),
+    };
+    let mut outbound: HashMap<u32, Vec<GuidebookLink>> = HashMap::new();
+    for link in existing {
+      outbound
+        .entry(link.source_object_id)
+        .or_default()
+        .push(link.clone());
+    }
+
+    diff_session_links(
+      1,
+      intended,
+      &outbound,
+      &HashMap::new(),
+      &scope,
+      &created_link_ids.iter().copied().collect(),
     )
-    .await
-    .context(
-      "failed to create outbound link from session during synchronization",
-    )?;
-    debug!(
-      source_presenter_id,
-      target_session_id, "added inbound link from presenter to session"
+  }
+
+  #[test]
+  fn leaves_hand_made_links_between_managed_sessions_alone() {
+    let plan = diff(
+      &[(1, Vec::new()), (2, Vec::new())],
+      &[session_link(10, 1, 2)],
+      &[],
     );
+
+    assert!(plan.is_empty());
   }
 
-  Ok(())
+  #[test]
+  fn deletes_created_links_to_sessions_that_are_no_longer_peers() {
+    let plan = diff(
+      &[(1, Vec::new()), (2, Vec::new())],
+      &[session_link(10, 1, 2)],
+      &[10],
+    );
+
+    assert_eq!(plan.links_to_delete.len(), 1);
+    assert_eq!(plan.links_to_delete[0].id, Some(10));
+    assert!(plan.links_to_create.is_empty());
+  }
+
+  #[test]
+  fn counts_hand_made_links_to_intended_peers() {
+    let plan =
+      diff(&[(1, vec![2]), (2, vec![1])], &[session_link(10, 1, 2)], &[
+      ]);
+
+    assert!(plan.links_to_delete.is_empty());
+    assert_eq!(plan.links_to_create.len(), 1);
+    let link = &plan.links_to_create[0];
+    assert_eq!((link.source_object_id, link.target_object_id), (2, 1));
+  }
 }
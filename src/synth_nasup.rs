@@ -7,33 +7,41 @@ use serde::Serialize;
 use tracing::{debug, warn};
 
 use crate::parse_nasup::parse_model::{
-  ParsedNasupLocation, ParsedNasupPresenterWithInstitutionBySession,
+  ParsedNasupAgendaItem, ParsedNasupLocation,
+  ParsedNasupPresenterWithInstitutionBySession, ParsedNasupRichText,
   ParsedNasupSession, ParsedNasupSessionType,
-  ParsedNasupStrandAndIntendedAudience,
+  ParsedNasupStrandAndIntendedAudience, ParsedNasupStrandMetadata,
 };
 
 #[derive(Clone, Debug, Serialize)]
 pub struct NasupSession {
   pub start_datetime:      chrono::DateTime<Utc>,
-  pub end_datetime:        chrono::DateTime<Utc>,
+  pub end_datetime:        Option<chrono::DateTime<Utc>>,
   pub room:                ParsedNasupLocation,
+  pub rooms:               Vec<ParsedNasupLocation>,
   pub session_type:        ParsedNasupSessionType,
   pub title:               String,
-  pub description:         String,
+  pub description:         ParsedNasupRichText,
+  pub capacity:            Option<u32>,
   /// Only presenters that have paid are included here.
   pub approved_presenters: Vec<NasupPresenter>,
-  pub strand:              Option<String>,
-  pub intended_audience:   Option<String>,
+  /// Presenters that haven't paid, who are only named as co-authors.
+  pub co_authors:          Vec<NasupPresenter>,
+  pub strands:             Vec<String>,
+  /// Display metadata for those strands the metadata sheet has any for.
+  pub strand_metadata:     Vec<ParsedNasupStrandMetadata>,
+  pub intended_audience:   Vec<String>,
   pub rank:                f32,
 }
 
 impl NasupSession {
+  /// The import ID of the session. The end time is left out, since it can be
+  /// changed or cleared without the session becoming another one.
   pub fn primary_key(&self) -> String {
     let session_primary_key = serde_json::json!({
       "name": self.title.get(0..30).unwrap_or(&self.title),
       "room": self.room.name,
       "start": self.start_datetime,
-      "end": self.end_datetime,
     });
 
     serde_json::to_string(&session_primary_key)
@@ -43,11 +51,45 @@ impl NasupSession {
   }
 }
 
+/// The current form of a session import ID from before the end time was
+/// left out of it, or `None` if it isn't one.
+pub fn migrate_legacy_primary_key(import_id: &str) -> Option<String> {
+  let serde_json::Value::Object(mut key) =
+    serde_json::from_str(import_id).ok()?
+  else {
+    return None;
+  };
+  key.remove("end")?;
+  serde_json::to_string(&key).ok()
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NasupAgendaItem {
+  pub start_datetime: chrono::DateTime<Utc>,
+  pub end_datetime:   Option<chrono::DateTime<Utc>>,
+  pub all_day:        bool,
+  pub title:          String,
+  pub rooms:          Vec<ParsedNasupLocation>,
+  pub description:    ParsedNasupRichText,
+}
+
+impl NasupAgendaItem {
+  /// The import ID of the session for this item, which is namespaced apart
+  /// from those of submitted sessions.
+  pub fn import_id(&self) -> String {
+    format!("agenda:{}:{}", self.start_datetime.to_rfc3339(), self.title)
+  }
+}
+
 #[derive(Clone, Debug, Serialize, Hash, PartialEq, Eq)]
 pub struct NasupPresenter {
   pub name:               String,
   pub first_institution:  Option<String>,
   pub second_institution: Option<String>,
+  pub bio:                Option<String>,
+  pub email:              Option<String>,
+  /// A headshot URL, or a file name in the headshots directory.
+  pub photo:              Option<String>,
 }
 
 pub fn synthesize_parsed_nasup_data(
@@ -56,34 +98,16 @@ pub fn synthesize_parsed_nasup_data(
     ParsedNasupPresenterWithInstitutionBySession,
   >,
   parsed_strands: Vec<ParsedNasupStrandAndIntendedAudience>,
+  parsed_strand_metadata: Vec<ParsedNasupStrandMetadata>,
 ) -> miette::Result<Vec<NasupSession>> {
   let mut synthesized_sessions = Vec::new();
 
   for parsed_session in parsed_sessions {
-    let start_datetime = Eastern
-      .with_ymd_and_hms(
-        parsed_session.date.year(),
-        parsed_session.date.month(),
-        parsed_session.date.day(),
-        parsed_session.start_time.hour(),
-        parsed_session.start_time.minute(),
-        parsed_session.start_time.second(),
-      )
-      .single()
-      .expect("super crazy time weirdness")
-      .to_utc();
-    let end_datetime = Eastern
-      .with_ymd_and_hms(
-        parsed_session.date.year(),
-        parsed_session.date.month(),
-        parsed_session.date.day(),
-        parsed_session.end_time.hour(),
-        parsed_session.end_time.minute(),
-        parsed_session.end_time.second(),
-      )
-      .single()
-      .expect("super crazy time weirdness")
-      .to_utc();
+    let start_datetime =
+      conference_datetime(parsed_session.date, parsed_session.start_time);
+    let end_datetime = parsed_session
+      .end_time
+      .map(|end_time| conference_datetime(parsed_session.date, end_time));
 
     let session_name_search_query =
       strip_session_discriminators_from_name(&parsed_session.title);
@@ -103,34 +127,31 @@ pub fn synthesize_parsed_nasup_data(
     }
 
     let mut approved_presenters = Vec::new();
-
-    let paid_presenter_iter =
-      parsed_session.presenters.iter().filter(|p| p.paid);
-    for paid_presenter in paid_presenter_iter {
+    let mut co_authors = Vec::new();
+    for presenter in &parsed_session.presenters {
       let record = relevant_presenter_institution_records
         .iter()
-        .find(|r| r.name == paid_presenter.name);
-
-      match record {
-        Some(record) => {
-          approved_presenters.push(NasupPresenter {
-            name:               paid_presenter.name.clone(),
-            first_institution:  record.first_institution.clone(),
-            second_institution: record.second_institution.clone(),
-          });
-        }
-        None => {
-          warn!(
-            name = paid_presenter.name,
-            session = session_name_search_query,
-            "could not find presenter-institution-session for presenter"
-          );
-          approved_presenters.push(NasupPresenter {
-            name:               paid_presenter.name.clone(),
-            first_institution:  None,
-            second_institution: None,
-          });
-        }
+        .find(|r| r.name == presenter.name);
+      if record.is_none() && presenter.paid {
+        warn!(
+          name = presenter.name,
+          session = session_name_search_query,
+          "could not find presenter-institution-session for presenter"
+        );
+      }
+
+      let nasup_presenter = NasupPresenter {
+        name:               presenter.name.clone(),
+        first_institution:  record.and_then(|r| r.first_institution.clone()),
+        second_institution: record.and_then(|r| r.second_institution.clone()),
+        bio:                record.and_then(|r| r.bio.clone()),
+        email:              record.and_then(|r| r.email.clone()),
+        photo:              record.and_then(|r| r.photo.clone()),
+      };
+      if presenter.paid {
+        approved_presenters.push(nasup_presenter);
+      } else {
+        co_authors.push(nasup_presenter);
       }
     }
 
@@ -146,19 +167,30 @@ pub fn synthesize_parsed_nasup_data(
         "found no strands records with given session title",
       );
     }
-    if relevant_strands_records.len() > 1 {
-      warn!(
-        session = session_name_search_query,
-        "found more than one strands record with given session title",
-      );
-    }
 
-    let record = relevant_strands_records.first();
-    let strand = record.map(|r| r.strand.clone());
-    let intended_audience = record.map(|r| r.intended_audience.clone());
+    // merge the strands and audiences of every record, keeping their order
+    let mut strands = Vec::new();
+    let mut intended_audience = Vec::new();
+    for record in &relevant_strands_records {
+      for strand in &record.strands {
+        if !strands.contains(strand) {
+          strands.push(strand.clone());
+        }
+      }
+      for audience in &record.intended_audience {
+        if !intended_audience.contains(audience) {
+          intended_audience.push(audience.clone());
+        }
+      }
+    }
+    let strand_metadata = parsed_strand_metadata
+      .iter()
+      .filter(|m| strands.contains(&m.strand))
+      .cloned()
+      .collect();
 
     debug!(
-      ?strand,
+      ?strands,
       ?intended_audience,
       session = session_name_search_query,
       "found strands for session"
@@ -168,11 +200,15 @@ pub fn synthesize_parsed_nasup_data(
       start_datetime,
       end_datetime,
       room: parsed_session.room,
+      rooms: parsed_session.rooms,
       session_type: parsed_session.session_type,
       title: parsed_session.title,
       description: parsed_session.description,
+      capacity: parsed_session.capacity,
       approved_presenters,
-      strand,
+      co_authors,
+      strands,
+      strand_metadata,
       intended_audience,
       rank: parsed_session.row_index as f32,
     };
@@ -182,9 +218,62 @@ pub fn synthesize_parsed_nasup_data(
     synthesized_sessions.push(synthesized_session);
   }
 
+  for metadata in parsed_strand_metadata.iter().filter(|m| {
+    !synthesized_sessions
+      .iter()
+      .any(|s| s.strands.contains(&m.strand))
+  }) {
+    warn!(
+      strand = metadata.strand,
+      "found strand metadata for a strand no session has"
+    );
+  }
+
   Ok(synthesized_sessions)
 }
 
+/// Converts a spreadsheet date and time, which are in conference time, to
+/// UTC.
+fn conference_datetime(
+  date: chrono::NaiveDate,
+  time: chrono::NaiveTime,
+) -> chrono::DateTime<Utc> {
+  Eastern
+    .with_ymd_and_hms(
+      date.year(),
+      date.month(),
+      date.day(),
+      time.hour(),
+      time.minute(),
+      time.second(),
+    )
+    .single()
+    .expect("super crazy time weirdness")
+    .to_utc()
+}
+
+pub fn synthesize_parsed_nasup_agenda_items(
+  parsed_agenda_items: Vec<ParsedNasupAgendaItem>,
+) -> Vec<NasupAgendaItem> {
+  parsed_agenda_items
+    .into_iter()
+    .map(|item| NasupAgendaItem {
+      // all-day items start at midnight of their day
+      start_datetime: conference_datetime(
+        item.date,
+        item.start_time.unwrap_or_default(),
+      ),
+      end_datetime:   item
+        .end_time
+        .map(|end_time| conference_datetime(item.date, end_time)),
+      all_day:        item.start_time.is_none(),
+      title:          item.title,
+      rooms:          item.rooms,
+      description:    item.description,
+    })
+    .collect()
+}
+
 pub fn strip_session_discriminators_from_name(mut input: &str) -> String {
   let prefixes_to_strip = [
     "A: ", "B: ", "RT 1: ", "RT 2: ", "RT 3: ", "RT 4: ", "RT 5: ", "RT 6: ",
@@ -204,3 +293,88 @@ pub fn strip_session_discriminators_from_name(mut input: &str) -> String {
   }
   input.to_owned()
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::parse_nasup::parse_model::PairedConcurrentDiscriminant;
+
+  fn session(end: Option<(u32, u32)>) -> NasupSession {
+    let at = |(h, m)| Utc.with_ymd_and_hms(2026, 3, 5, h, m, 0).unwrap();
+    let room = ParsedNasupLocation::from_string("Room 101");
+    NasupSession {
+      start_datetime:      at((14, 0)),
+      end_datetime:        end.map(at),
+      room:                room.clone(),
+      rooms:               vec![room],
+      session_type:        ParsedNasupSessionType::PairedConcurrent(
+        PairedConcurrentDiscriminant::A,
+      ),
+      title:               "A: Tutoring".to_owned(),
+      description:         Default::default(),
+      capacity:            None,
+      approved_presenters: Vec::new(),
+      co_authors:          Vec::new(),
+      strands:             Vec::new(),
+      strand_metadata:     Vec::new(),
+      intended_audience:   Vec::new(),
+      rank:                0.0,
+    }
+  }
+
+  #[test]
+  fn keeps_import_id_when_end_time_changes_or_is_removed() {
+    let ending = session(Some((14, 25)));
+
+    assert_eq!(ending.primary_key(), session(None).primary_key());
+    assert_eq!(ending.primary_key(), session(Some((15, 0))).primary_key());
+  }
+
+  #[test]
+  fn migrates_import_ids_with_end_times() {
+    let ending = session(Some((14, 25)));
+    let legacy = serde_json::to_string(&serde_json::json!({
+      "name": ending.title,
+      "room": ending.room.name,
+      "start": ending.start_datetime,
+      "end": ending.end_datetime,
+    }))
+    .unwrap();
+
+    assert_eq!(
+      migrate_legacy_primary_key(&legacy),
+      Some(ending.primary_key())
+    );
+    assert_eq!(migrate_legacy_primary_key(&ending.primary_key()), None);
+    assert_eq!(migrate_legacy_primary_key("agenda:2026-03-05:Lunch"), None);
+  }
+
+  #[test]
+  fn strips_discriminator_prefixes() {
+    assert_eq!(
+      strip_session_discriminators_from_name("A: Tutoring"),
+      "Tutoring"
+    );
+    assert_eq!(
+      strip_session_discriminators_from_name("RT 3: Mentoring"),
+      "Mentoring"
+    );
+  }
+
+  #[test]
+  fn strips_repeated_and_indented_prefixes() {
+    assert_eq!(
+      strip_session_discriminators_from_name("  B: RT 1: Advising"),
+      "Advising"
+    );
+  }
+
+  #[test]
+  fn leaves_other_titles_alone() {
+    assert_eq!(strip_session_discriminators_from_name("Keynote"), "Keynote");
+    assert_eq!(
+      strip_session_discriminators_from_name("Plan A: Outreach"),
+      "Plan A: Outreach"
+    );
+  }
+}
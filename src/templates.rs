@@ -0,0 +1,354 @@
+use miette::{bail, miette};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// The default session description: the rendered description, then the
+/// presenter roster if enabled, then who the session is intended for.
+const DEFAULT_SESSION_DESCRIPTION: &str =
+  "{{{description_html}}}{{{presenter_roster_html}}}{{#\
+   audience}}<br><p><b>Intended for</b>: {{audience}}</p>{{/audience}}";
+
+/// The default presenter subtitle: the presenter's institutions.
+const DEFAULT_PRESENTER_SUBTITLE: &str = "{{institution}}";
+
+/// Templates for the text of Guidebook entities, which the program committee
+/// can change without code changes.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TemplateSettings {
+  pub session_description:   Template,
+  pub presenter_subtitle:    Template,
+  /// Presenter descriptions are left to the builder without a template.
+  pub presenter_description: Option<Template>,
+}
+
+impl Default for TemplateSettings {
+  fn default() -> Self {
+    Self {
+      session_description:   Template::parse(DEFAULT_SESSION_DESCRIPTION)
+        .expect("default session description template should parse"),
+      presenter_subtitle:    Template::parse(DEFAULT_PRESENTER_SUBTITLE)
+        .expect("default presenter subtitle template should parse"),
+      presenter_description: None,
+    }
+  }
+}
+
+/// A logic-less template in a subset of Mustache, rendered against JSON.
+///
+/// - `{{name}}` inserts a value, escaped when rendering HTML, and `{{{name}}}`
+///   or `{{&name}}` inserts it as is. Dotted names look into objects, and `.`
+///   is the current value.
+/// - `{{#name}}...{{/name}}` repeats for each item of a list, and otherwise
+///   renders once if the value is truthy.
+/// - `{{^name}}...{{/name}}` renders if the value is falsy or an empty list.
+/// - `{{! comment }}` is ignored.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Template {
+  nodes: Vec<Node>,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+  Text(String),
+  Variable {
+    name:   String,
+    escape: bool,
+  },
+  Section {
+    name:     String,
+    inverted: bool,
+    children: Vec<Node>,
+  },
+}
+
+impl TryFrom<String> for Template {
+  type Error = miette::Report;
+
+  fn try_from(source: String) -> miette::Result<Self> {
+    Template::parse(&source)
+  }
+}
+
+impl Template {
+  pub fn parse(source: &str) -> miette::Result<Self> {
+    // each open section, with the nodes before it
+    let mut stack: Vec<(String, bool, Vec<Node>)> = Vec::new();
+    let mut nodes = Vec::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find("{{") {
+      if start > 0 {
+        nodes.push(Node::Text(rest[..start].to_owned()));
+      }
+      let tag = &rest[start..];
+      let (inner, close) = match tag.strip_prefix("{{{") {
+        Some(inner) => (inner, "}}}"),
+        None => (&tag[2..], "}}"),
+      };
+      let Some(end) = inner.find(close) else {
+        bail!("found unclosed tag at {:?}", truncate(tag));
+      };
+      rest = &inner[end + close.len()..];
+      let content = inner[..end].trim();
+
+      let name = |s: &str| {
+        let s = s.trim();
+        if s.is_empty() {
+          Err(miette!("found tag without a name at {:?}", truncate(tag)))
+        } else {
+          Ok(s.to_owned())
+        }
+      };
+      if close == "}}}" {
+        nodes.push(Node::Variable {
+          name:   name(content)?,
+          escape: false,
+        });
+        continue;
+      }
+      match content.chars().next() {
+        Some('!') => {}
+        Some('&') => nodes.push(Node::Variable {
+          name:   name(&content[1..])?,
+          escape: false,
+        }),
+        Some(sigil @ ('#' | '^')) => {
+          let parent = std::mem::take(&mut nodes);
+          stack.push((name(&content[1..])?, sigil == '^', parent));
+        }
+        Some('/') => {
+          let closing = name(&content[1..])?;
+          let Some((opened, inverted, parent)) = stack.pop() else {
+            bail!("found {{{{/{closing}}}}} without an open section");
+          };
+          if opened != closing {
+            bail!("found {{{{/{closing}}}}} while {opened:?} is still open");
+          }
+          let children = std::mem::replace(&mut nodes, parent);
+          nodes.push(Node::Section {
+            name: opened,
+            inverted,
+            children,
+          });
+        }
+        _ => nodes.push(Node::Variable {
+          name:   name(content)?,
+          escape: true,
+        }),
+      }
+    }
+    if !rest.is_empty() {
+      nodes.push(Node::Text(rest.to_owned()));
+    }
+    if let Some((opened, ..)) = stack.last() {
+      bail!("found section {opened:?} that is never closed");
+    }
+
+    Ok(Template { nodes })
+  }
+
+  /// Renders the template as HTML, escaping inserted values.
+  pub fn render_html(&self, context: &Value) -> String {
+    render_nodes(&self.nodes, &mut vec![context], true)
+  }
+
+  /// Renders the template as plain text, inserting values as is.
+  pub fn render_text(&self, context: &Value) -> String {
+    render_nodes(&self.nodes, &mut vec![context], false)
+  }
+}
+
+fn render_nodes(nodes: &[Node], stack: &mut Vec<&Value>, html: bool) -> String {
+  let mut output = String::new();
+  for node in nodes {
+    match node {
+      Node::Text(text) => output.push_str(text),
+      Node::Variable { name, escape } => {
+        let text = match lookup(stack, name) {
+          None | Some(Value::Null) => String::new(),
+          Some(Value::String(s)) => s.clone(),
+          Some(value) => value.to_string(),
+        };
+        if html && *escape {
+          output.push_str(&html_escape::encode_text(&text));
+        } else {
+          output.push_str(&text);
+        }
+      }
+      Node::Section {
+        name,
+        inverted,
+        children,
+      } => {
+        let value = lookup(stack, name);
+        let items = match value {
+          None | Some(Value::Null | Value::Bool(false)) => Vec::new(),
+          Some(Value::String(s)) if s.is_empty() => Vec::new(),
+          Some(Value::Array(items)) => items.iter().collect(),
+          Some(value) => vec![value],
+        };
+        if *inverted {
+          if items.is_empty() {
+            output.push_str(&render_nodes(children, stack, html));
+          }
+          continue;
+        }
+        for item in items {
+          stack.push(item);
+          output.push_str(&render_nodes(children, stack, html));
+          stack.pop();
+        }
+      }
+    }
+  }
+  output
+}
+
+/// Looks up a dotted name, starting from the innermost value that has its
+/// first part.
+fn lookup<'a>(stack: &[&'a Value], name: &str) -> Option<&'a Value> {
+  if name == "." {
+    return stack.last().copied();
+  }
+  let mut parts = name.split('.');
+  let first = parts.next()?;
+  let value = stack.iter().rev().find_map(|v| v.get(first))?;
+  parts.try_fold(value, |value, part| match value {
+    Value::Array(items) => items.get(part.parse::<usize>().ok()?),
+    value => value.get(part),
+  })
+}
+
+fn truncate(text: &str) -> String { text.chars().take(30).collect() }
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn render_html(source: &str, context: Value) -> String {
+    Template::parse(source).unwrap().render_html(&context)
+  }
+
+  #[test]
+  fn inserts_variables() {
+    let context = json!({ "name": "Ada", "count": 3, "missing": null });
+
+    assert_eq!(
+      render_html("{{name}} has {{ count }}{{missing}}{{absent}}", context),
+      "Ada has 3"
+    );
+  }
+
+  #[test]
+  fn escapes_variables_only_in_html() {
+    let template = Template::parse("{{text}}|{{{text}}}|{{&text}}").unwrap();
+    let context = json!({ "text": "<b>A & B</b>" });
+
+    assert_eq!(
+      template.render_html(&context),
+      "&lt;b&gt;A &amp; B&lt;/b&gt;|<b>A & B</b>|<b>A & B</b>"
+    );
+    assert_eq!(
+      template.render_text(&context),
+      "<b>A & B</b>|<b>A & B</b>|<b>A & B</b>"
+    );
+  }
+
+  #[test]
+  fn repeats_sections_over_lists() {
+    let context = json!({
+      "presenters": [{ "name": "Ada" }, { "name": "Grace" }],
+      "tags": ["a", "b"],
+      "title": "Talk",
+    });
+
+    assert_eq!(
+      render_html(
+        "{{#presenters}}<li>{{name}}, \
+         {{title}}</li>{{/presenters}}{{#tags}}{{.}};{{/tags}}",
+        context
+      ),
+      "<li>Ada, Talk</li><li>Grace, Talk</li>a;b;"
+    );
+  }
+
+  #[test]
+  fn renders_sections_once_for_truthy_values() {
+    let source = "{{#value}}[{{.}}]{{/value}}";
+
+    assert_eq!(render_html(source, json!({ "value": "x" })), "[x]");
+    assert_eq!(render_html(source, json!({ "value": true })), "[true]");
+    assert_eq!(render_html(source, json!({ "value": 0 })), "[0]");
+    assert_eq!(render_html(source, json!({ "value": "" })), "");
+    assert_eq!(render_html(source, json!({ "value": false })), "");
+    assert_eq!(render_html(source, json!({ "value": [] })), "");
+    assert_eq!(render_html(source, json!({})), "");
+  }
+
+  #[test]
+  fn renders_inverted_sections_for_falsy_values() {
+    let source = "{{^items}}none{{/items}}";
+
+    assert_eq!(render_html(source, json!({ "items": [] })), "none");
+    assert_eq!(render_html(source, json!({ "items": null })), "none");
+    assert_eq!(render_html(source, json!({})), "none");
+    assert_eq!(render_html(source, json!({ "items": [1] })), "");
+    assert_eq!(render_html(source, json!({ "items": "x" })), "");
+  }
+
+  #[test]
+  fn looks_up_dotted_names() {
+    let context = json!({
+      "session": { "room": { "name": "Hall A" } },
+      "rooms": ["Hall A", "Hall B"],
+    });
+
+    assert_eq!(
+      render_html(
+        "{{session.room.name}}, {{rooms.1}}, {{session.nope}}",
+        context
+      ),
+      "Hall A, Hall B, "
+    );
+  }
+
+  #[test]
+  fn ignores_comments() {
+    assert_eq!(render_html("a{{! note }}b", json!({})), "ab");
+  }
+
+  #[test]
+  fn rejects_malformed_templates() {
+    for source in [
+      "{{#open}}never closed",
+      "{{#outer}}{{#inner}}{{/outer}}{{/inner}}",
+      "{{/stray}}",
+      "{{unclosed",
+      "{{{unclosed}}",
+      "{{}}",
+    ] {
+      assert!(
+        Template::parse(source).is_err(),
+        "{source:?} should not parse"
+      );
+    }
+  }
+
+  #[test]
+  fn parses_default_templates() {
+    let settings = TemplateSettings::default();
+    let context = json!({
+      "description_html": "<p>About</p>",
+      "audience": "Faculty & staff",
+    });
+
+    assert_eq!(
+      settings.session_description.render_html(&context),
+      "<p>About</p><br><p><b>Intended for</b>: Faculty &amp; staff</p>"
+    );
+  }
+}
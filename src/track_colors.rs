@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+
+use colorutils_rs::{EuclideanDistance, Oklab, Oklch, Rgb, TransferFunction};
+use miette::bail;
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+/// How schedule tracks without an explicit color are colored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackColorStrategy {
+  /// A hue derived from a stable hash of the track name. Colors never change
+  /// when tracks are added, but two tracks can land on similar hues.
+  #[default]
+  HashedHues,
+  /// Hues spaced evenly over the sorted track list. Colors are as far apart
+  /// as possible, but shift when tracks are added or removed.
+  EvenHues,
+  /// Colors from `palette`, assigned over the sorted track list.
+  Palette,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrackColorSettings {
+  pub strategy:     TrackColorStrategy,
+  /// Hex colors used by the `palette` strategy.
+  pub palette:      Vec<String>,
+  /// Hex colors for specific tracks, keyed by track name.
+  pub overrides:    HashMap<String, String>,
+  /// The smallest OKLab distance two track colors may have before a warning.
+  pub min_distance: f32,
+  /// The smallest contrast ratio a track color may have against
+  /// `text_color` before a warning.
+  pub min_contrast: f32,
+  /// The hex color of text drawn on top of track colors.
+  pub text_color:   String,
+}
+
+impl Default for TrackColorSettings {
+  fn default() -> Self {
+    Self {
+      strategy:     TrackColorStrategy::default(),
+      palette:      Vec::new(),
+      overrides:    HashMap::new(),
+      min_distance: 0.04,
+      min_contrast: 4.5,
+      text_color:   "#000000".to_owned(),
+    }
+  }
+}
+
+impl TrackColorSettings {
+  pub fn validate(&self) -> miette::Result<()> {
+    if self.strategy == TrackColorStrategy::Palette && self.palette.is_empty() {
+      bail!("the palette strategy requires a non-empty `palette`");
+    }
+    for color in self
+      .palette
+      .iter()
+      .chain(self.overrides.values())
+      .chain(Some(&self.text_color))
+    {
+      if normalize_hex_color(color).is_none() {
+        bail!("{color:?} is not a hex color like \"#2E7D32\"");
+      }
+    }
+
+    Ok(())
+  }
+}
+
+/// Families of generated colors, so that kinds of tracks are told apart at a
+/// glance.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TrackColorFamily {
+  /// Saturated colors.
+  Vivid,
+  /// Grayish colors.
+  Muted,
+  /// Pale, light colors.
+  Pastel,
+}
+
+impl TrackColorFamily {
+  const ALL: [TrackColorFamily; 3] = [
+    TrackColorFamily::Vivid,
+    TrackColorFamily::Muted,
+    TrackColorFamily::Pastel,
+  ];
+
+  fn lightness_and_chroma(&self) -> (f32, f32) {
+    match self {
+      TrackColorFamily::Vivid => (0.7, 0.16),
+      TrackColorFamily::Muted => (0.7, 0.05),
+      TrackColorFamily::Pastel => (0.88, 0.08),
+    }
+  }
+}
+
+/// A schedule track that needs a color.
+#[derive(Clone, Debug)]
+pub struct TrackColorRequest {
+  pub name:     String,
+  pub family:   TrackColorFamily,
+  /// A color from the spreadsheet, which wins over everything else.
+  pub explicit: Option<String>,
+}
+
+/// Assigns a hex color to every requested track, in the order requested.
+pub fn assign_track_colors(
+  settings: &TrackColorSettings,
+  requests: &[TrackColorRequest],
+) -> Vec<String> {
+  let mut colors = vec![String::new(); requests.len()];
+  let mut generated = Vec::new();
+  for (i, request) in requests.iter().enumerate() {
+    let explicit = request
+      .explicit
+      .clone()
+      .or_else(|| settings.overrides.get(&request.name).cloned())
+      .and_then(|c| normalize_hex_color(&c));
+    match explicit {
+      Some(color) => colors[i] = color,
+      None => generated.push((i, request)),
+    }
+  }
+  // each family in name order, in the order the families are declared
+  generated.sort_by_key(|(_, r)| (r.family, r.name.clone()));
+
+  match settings.strategy {
+    TrackColorStrategy::HashedHues => {
+      for (i, request) in generated {
+        let hue = stable_hash(&request.name) as f64 / (u64::MAX as f64 / 360.0);
+        colors[i] = hex_color_from_hue(hue as f32, request.family);
+      }
+    }
+    TrackColorStrategy::EvenHues => {
+      for family in TrackColorFamily::ALL {
+        let group = generated.iter().filter(|(_, r)| r.family == family);
+        let count = group.clone().count();
+        for (n, (i, _)) in group.enumerate() {
+          let hue = n as f32 * 360.0 / count as f32;
+          colors[*i] = hex_color_from_hue(hue, family);
+        }
+      }
+    }
+    TrackColorStrategy::Palette => {
+      if generated.len() > settings.palette.len() {
+        warn!(
+          tracks = generated.len(),
+          palette = settings.palette.len(),
+          "found more tracks than palette colors, so colors will repeat"
+        );
+      }
+      for ((i, _), color) in
+        generated.into_iter().zip(settings.palette.iter().cycle())
+      {
+        colors[i] = normalize_hex_color(color).unwrap_or(color.clone());
+      }
+    }
+  }
+
+  colors
+}
+
+/// Warns about pairs of track colors that are hard to tell apart and about
+/// track colors that text is hard to read on.
+pub fn check_track_colors(
+  settings: &TrackColorSettings,
+  colors: &[(String, String)],
+) {
+  let text_color = parse_hex_color(&settings.text_color);
+  let parsed = colors
+    .iter()
+    .filter_map(|(name, color)| {
+      let rgb = parse_hex_color(color);
+      if rgb.is_none() {
+        warn!(name, color, "found track color that is not a hex color");
+      }
+      Some((name, color, rgb?))
+    })
+    .collect::<Vec<_>>();
+
+  for (i, (name, color, rgb)) in parsed.iter().enumerate() {
+    for (other_name, other_color, other_rgb) in &parsed[i + 1..] {
+      let distance =
+        Oklab::from_srgb(*rgb).euclidean_distance(Oklab::from_srgb(*other_rgb));
+      if distance < settings.min_distance {
+        warn!(
+          name,
+          color,
+          other_name,
+          other_color,
+          distance,
+          "found track colors that are perceptually too close"
+        );
+      }
+    }
+
+    if let Some(text_color) = text_color {
+      let contrast = contrast_ratio(*rgb, text_color);
+      if contrast < settings.min_contrast {
+        warn!(
+          name,
+          color,
+          contrast,
+          text_color = settings.text_color,
+          "found track color with too little contrast against text"
+        );
+      }
+    }
+  }
+  debug!(count = parsed.len(), "checked track colors");
+}
+
+/// Normalizes a hex color to `#RRGGBB`, accepting a missing `#` and
+/// lowercase digits.
+pub fn normalize_hex_color(color: &str) -> Option<String> {
+  let color = color.trim();
+  let color = color.strip_prefix('#').unwrap_or(color);
+  (color.len() == 6 && color.chars().all(|c| c.is_ascii_hexdigit()))
+    .then(|| format!("#{}", color.to_ascii_uppercase()))
+}
+
+fn parse_hex_color(color: &str) -> Option<Rgb<u8>> {
+  let color = normalize_hex_color(color)?;
+  let channel = |i: usize| u8::from_str_radix(&color[i..i + 2], 16).ok();
+  Some(Rgb::new(channel(1)?, channel(3)?, channel(5)?))
+}
+
+fn hex_color_from_hue(hue: f32, family: TrackColorFamily) -> String {
+  let (l, c) = family.lightness_and_chroma();
+  let color = Oklch { l, c, h: hue }.to_rgb(TransferFunction::Srgb);
+  format!(
+    "#{r:02X}{g:02X}{b:02X}",
+    r = color.r,
+    g = color.g,
+    b = color.b
+  )
+}
+
+/// The WCAG contrast ratio between two colors, from 1 to 21.
+fn contrast_ratio(a: Rgb<u8>, b: Rgb<u8>) -> f32 {
+  let luminance = |c: Rgb<u8>| {
+    let c = c.to_rgb_f32().linearize(TransferFunction::Srgb);
+    0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b
+  };
+  let (a, b) = (luminance(a), luminance(b));
+  (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is stable across Rust
+/// versions.
+fn stable_hash(text: &str) -> u64 {
+  text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+    (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn request(name: &str, family: TrackColorFamily) -> TrackColorRequest {
+    TrackColorRequest {
+      name: name.to_owned(),
+      family,
+      explicit: None,
+    }
+  }
+
+  fn settings(strategy: TrackColorStrategy) -> TrackColorSettings {
+    TrackColorSettings {
+      strategy,
+      palette: vec!["#111111".to_owned(), "222222".to_owned()],
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn normalizes_hex_colors() {
+    assert_eq!(normalize_hex_color(" 2e7d32 "), Some("#2E7D32".to_owned()));
+    assert_eq!(normalize_hex_color("#2E7D32"), Some("#2E7D32".to_owned()));
+    assert_eq!(normalize_hex_color("#2E7D3"), None);
+    assert_eq!(normalize_hex_color("green"), None);
+  }
+
+  #[test]
+  fn prefers_explicit_colors_then_overrides() {
+    let mut settings = settings(TrackColorStrategy::Palette);
+    settings
+      .overrides
+      .insert("Equity".to_owned(), "#abcdef".to_owned());
+    let requests = [
+      TrackColorRequest {
+        explicit: Some("#123456".to_owned()),
+        ..request("Equity", TrackColorFamily::Vivid)
+      },
+      request("Equity", TrackColorFamily::Vivid),
+      request("Access", TrackColorFamily::Vivid),
+    ];
+
+    assert_eq!(assign_track_colors(&settings, &requests), [
+      "#123456", "#ABCDEF", "#111111"
+    ]);
+  }
+
+  #[test]
+  fn assigns_palette_colors_by_family_then_name_and_repeats_them() {
+    let requests = [
+      request("Zeta", TrackColorFamily::Vivid),
+      request("Faculty", TrackColorFamily::Muted),
+      request("Alpha", TrackColorFamily::Vivid),
+    ];
+
+    assert_eq!(
+      assign_track_colors(&settings(TrackColorStrategy::Palette), &requests),
+      ["#222222", "#111111", "#111111"]
+    );
+  }
+
+  #[test]
+  fn spreads_even_hues_within_each_family() {
+    let requests = [
+      request("Alpha", TrackColorFamily::Vivid),
+      request("Beta", TrackColorFamily::Vivid),
+      request("Faculty", TrackColorFamily::Pastel),
+    ];
+
+    let colors =
+      assign_track_colors(&settings(TrackColorStrategy::EvenHues), &requests);
+    assert_ne!(colors[0], colors[1]);
+    // the first hue of every family is the same, so only the family differs
+    assert_eq!(colors[0], hex_color_from_hue(0.0, TrackColorFamily::Vivid));
+    assert_eq!(colors[2], hex_color_from_hue(0.0, TrackColorFamily::Pastel));
+  }
+
+  #[test]
+  fn keeps_hashed_hues_when_tracks_are_added() {
+    let settings = settings(TrackColorStrategy::HashedHues);
+    let alone = assign_track_colors(&settings, &[request(
+      "Alpha",
+      TrackColorFamily::Vivid,
+    )]);
+    let with_others = assign_track_colors(&settings, &[
+      request("Beta", TrackColorFamily::Vivid),
+      request("Alpha", TrackColorFamily::Vivid),
+    ]);
+
+    assert_eq!(alone[0], with_others[1]);
+    assert!(normalize_hex_color(&alone[0]).is_some());
+  }
+
+  #[test]
+  fn computes_wcag_contrast_ratios() {
+    let black = Rgb::new(0, 0, 0);
+    let white = Rgb::new(255, 255, 255);
+
+    assert!((contrast_ratio(black, white) - 21.0).abs() < 0.01);
+    assert!((contrast_ratio(white, white) - 1.0).abs() < 0.01);
+  }
+
+  #[test]
+  fn rejects_palette_strategy_without_palette() {
+    let settings = TrackColorSettings {
+      strategy: TrackColorStrategy::Palette,
+      ..Default::default()
+    };
+
+    assert!(settings.validate().is_err());
+  }
+}
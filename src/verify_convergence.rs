@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use miette::Context;
+use tracing::{error, info, instrument};
+
+use crate::{
+  config::Config,
+  guidebook::{
+    fetch_all_guidebook_entities,
+    model::{
+      GuidebookLocation, GuidebookPresenter, GuidebookScheduleTrack,
+      GuidebookSession,
+    },
+  },
+  nasup_to_guidebook::{
+    nasup_agenda_items_to_guidebook_sessions,
+    nasup_sessions_to_guidebook_locations,
+    nasup_sessions_to_guidebook_presenters,
+    nasup_sessions_to_guidebook_schedule_tracks,
+    nasup_sessions_to_guidebook_sessions,
+  },
+  reconcile_guidebook_custom_lists::{
+    fetch_existing_custom_list_items, fetch_intended_custom_lists,
+    link_custom_list_items_to_sessions, plan_institution_links,
+    reconcile_intended_and_existing_guidebook_custom_list_items,
+  },
+  reconcile_guidebook_locations::reconcile_intended_and_existing_guidebook_locations,
+  reconcile_guidebook_presenters::reconcile_intended_and_existing_guidebook_presenters,
+  reconcile_guidebook_sessions::reconcile_intended_and_existing_guidebook_sessions,
+  reconcile_guidebook_strands::reconcile_intended_and_existing_guidebook_schedule_tracks,
+  synchronize_links::{
+    LinkScope, plan_session_links, resolve_intended_session_links,
+  },
+  synth_nasup::{NasupAgendaItem, NasupSession},
+};
+
+/// Re-fetches every entity kind after a sync and recomputes every
+/// reconciliation without executing any of them. Anything still left to do
+/// means Guidebook ignored or normalized part of what we sent.
+///
+/// The reconciliations run without sync bases, since the sync just recorded
+/// the intended values as bases, against which a field Guidebook ignored
+/// looks like one edited in the builder and is left alone.
+#[instrument(skip(config, sessions, agenda_items))]
+pub async fn verify_convergence(
+  config: &Config,
+  sessions: Vec<NasupSession>,
+  agenda_items: Vec<NasupAgendaItem>,
+) -> miette::Result<()> {
+  let mut residual_kinds = Vec::new();
+  let no_sync_bases = HashMap::new();
+
+  let existing_strands =
+    fetch_all_guidebook_entities::<GuidebookScheduleTrack>(
+      config,
+      "/schedule-tracks",
+    )
+    .await?;
+  let strands_reconciliation =
+    reconcile_intended_and_existing_guidebook_schedule_tracks(
+      &nasup_sessions_to_guidebook_schedule_tracks(
+        config,
+        &sessions,
+        &agenda_items,
+      )?,
+      &existing_strands,
+      &no_sync_bases,
+    )
+    .context("failed to recompute schedule track reconciliation")?;
+  if !strands_reconciliation.is_empty() {
+    error!(?strands_reconciliation, "schedule tracks did not converge");
+    residual_kinds.push("schedule tracks");
+  }
+
+  let existing_locations =
+    fetch_all_guidebook_entities::<GuidebookLocation>(config, "/locations")
+      .await?;
+  let locations_reconciliation =
+    reconcile_intended_and_existing_guidebook_locations(
+      &config.settings,
+      &nasup_sessions_to_guidebook_locations(config, &sessions, &agenda_items)?,
+      &existing_locations,
+      &no_sync_bases,
+    )
+    .context("failed to recompute location reconciliation")?;
+  if !locations_reconciliation.is_empty() {
+    error!(?locations_reconciliation, "locations did not converge");
+    residual_kinds.push("locations");
+  }
+
+  let existing_presenters = fetch_all_guidebook_entities::<GuidebookPresenter>(
+    config,
+    &format!(
+      "/custom-list-items/?custom_lists={list_id}",
+      list_id = config.presenter_list_id()?
+    ),
+  )
+  .await?;
+  let presenter_reconciliation =
+    reconcile_intended_and_existing_guidebook_presenters(
+      &nasup_sessions_to_guidebook_presenters(config, &sessions)?,
+      &existing_presenters,
+      &no_sync_bases,
+    )
+    .context("failed to recompute presenter reconciliation")?;
+  if !presenter_reconciliation.is_empty() {
+    error!(?presenter_reconciliation, "presenters did not converge");
+    residual_kinds.push("presenters");
+  }
+
+  let intended_custom_lists =
+    fetch_intended_custom_lists(config, &sessions).await?;
+  let existing_custom_list_items =
+    fetch_existing_custom_list_items(config, &intended_custom_lists).await?;
+  for list in &intended_custom_lists {
+    let custom_list_reconciliation =
+      reconcile_intended_and_existing_guidebook_custom_list_items(
+        list,
+        existing_custom_list_items
+          .get(&list.list_id)
+          .map_or(&[], Vec::as_slice),
+        &no_sync_bases,
+      )
+      .context("failed to recompute custom list reconciliation")?;
+    if !custom_list_reconciliation.is_empty() {
+      error!(?custom_list_reconciliation, "custom list did not converge");
+      residual_kinds.push("custom list items");
+    }
+  }
+
+  let institution_links_plan = plan_institution_links(
+    config,
+    &sessions,
+    &existing_presenters,
+    &existing_custom_list_items,
+  )
+  .await
+  .context("failed to recompute institution link plan")?;
+  if let Some(plan) = institution_links_plan
+    && !plan.is_empty()
+  {
+    error!(?plan, "institution links did not converge");
+    residual_kinds.push("institution links");
+  }
+
+  let (mut intended_sessions, mut import_id_to_links_map) =
+    nasup_sessions_to_guidebook_sessions(
+      config,
+      sessions.clone(),
+      &existing_strands,
+      &existing_locations,
+      &existing_presenters,
+    )?;
+  intended_sessions.extend(nasup_agenda_items_to_guidebook_sessions(
+    config,
+    &agenda_items,
+    &existing_strands,
+    &existing_locations,
+  )?);
+  let custom_list_item_ids = link_custom_list_items_to_sessions(
+    &intended_custom_lists,
+    &existing_custom_list_items,
+    &sessions,
+    &mut import_id_to_links_map,
+  );
+  let existing_sessions =
+    fetch_all_guidebook_entities::<GuidebookSession>(config, "/sessions")
+      .await?;
+  let session_reconciliation =
+    reconcile_intended_and_existing_guidebook_sessions(
+      &intended_sessions,
+      &existing_sessions,
+      &no_sync_bases,
+    )
+    .context("failed to recompute session reconciliation")?;
+  if !session_reconciliation.is_empty() {
+    error!(?session_reconciliation, "sessions did not converge");
+    residual_kinds.push("sessions");
+  }
+
+  let intended_session_links =
+    resolve_intended_session_links(import_id_to_links_map, &existing_sessions);
+  let link_scope = LinkScope {
+    custom_list_item_ids: existing_presenters
+      .iter()
+      .filter_map(|p| p.id)
+      .chain(custom_list_item_ids)
+      .collect(),
+    session_ids:          intended_session_links.keys().copied().collect(),
+  };
+  let links_plan =
+    plan_session_links(config, intended_session_links, &link_scope)
+      .await
+      .context("failed to recompute link plan")?;
+  if !links_plan.is_empty() {
+    error!(?links_plan, "links did not converge");
+    residual_kinds.push("links");
+  }
+
+  if !residual_kinds.is_empty() {
+    miette::bail!(
+      "found residual diffs after sync in: {}",
+      residual_kinds.join(", ")
+    );
+  }
+  info!("verified that guidebook state converged");
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::guidebook::model::SyncBase;
+
+  fn session(id: Option<u32>, name: &str) -> GuidebookSession {
+    serde_json::from_value(serde_json::json!({
+      "id": id,
+      "guide": 1,
+      "name": name,
+      "import_id": "session",
+    }))
+    .unwrap()
+  }
+
+  #[test]
+  fn reports_fields_guidebook_ignored_after_recording_their_base() {
+    let intended = [session(None, "Tutoring: A Primer")];
+    // guidebook normalized the name, while the sync recorded what it sent
+    let existing = [session(Some(7), "Tutoring - A Primer")];
+    let recorded_bases = HashMap::from([(7, SyncBase::of(&intended[0]))]);
+
+    let against_bases = reconcile_intended_and_existing_guidebook_sessions(
+      &intended,
+      &existing,
+      &recorded_bases,
+    )
+    .unwrap();
+    let without_bases = reconcile_intended_and_existing_guidebook_sessions(
+      &intended,
+      &existing,
+      &HashMap::new(),
+    )
+    .unwrap();
+
+    assert!(against_bases.is_empty());
+    assert!(!without_bases.is_empty());
+  }
+}